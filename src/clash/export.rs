@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use super::{link_to_proxy, ClashConfig, ClashExport, ClashGroup, CLASH_GROUP_NAME};
+use crate::models::{Outbound, SingBoxConfig, Unmapped};
+
+
+/// Port of the mixed inbound when the template has none
+const CLASH_MIXED_PORT: u16 = 7890;
+
+/// Ranges `ip_is_private` matches, written out so the profile needs no GeoIP database
+const PRIVATE_CIDRS: &[&str] = &[
+    "10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16", "100.64.0.0/10", "127.0.0.0/8",
+    "169.254.0.0/16", "::1/128", "fc00::/7", "fe80::/10",
+];
+
+/// Whole seconds of a Go duration such as `3m` or `1m30s`, the way sing-box
+/// writes intervals
+fn duration_seconds(duration: &str) -> Option<u64> {
+    let mut seconds = 0.0;
+    let mut rest = duration.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let value: f64 = rest[..split].parse().ok()?;
+        let unit_len = rest[split..].find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len() - split);
+        let scale = match &rest[split..split + unit_len] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            "us" | "µs" => 0.000_001,
+            "ns" => 0.000_000_001,
+            _ => return None,
+        };
+        seconds += value * scale;
+        rest = &rest[split + unit_len..];
+    }
+    Some(seconds as u64)
+}
+
+impl SingBoxConfig {
+    /// Port of the mixed inbound of the template
+    fn mixed_port(&self) -> Option<u16> {
+        self.inbounds.iter()
+            .find(|inbound| inbound.type_field == "mixed")
+            .and_then(|inbound| inbound.extra.get("listen_port"))
+            .and_then(|port| port.as_u64())
+            .and_then(|port| u16::try_from(port).ok())
+    }
+
+    /// Renders the proxies and `route` of this config as a mihomo (Clash Meta)
+    /// profile. Selectors and urltests become `select` and `url-test` groups; a config
+    /// without groups gets a `PROXY` group offering every proxy, `route.final` first.
+    ///
+    /// Values of the destination fields of a route rule are alternatives, so each
+    /// becomes a rule of its own; a rule that also matches ports or processes becomes an
+    /// `AND` rule. `route.final` becomes `MATCH`. Whatever mihomo cannot express is left
+    /// out and listed in [`ClashExport::unmapped`].
+    pub fn to_clash(&self) -> ClashExport {
+        let mut unmapped = Vec::new();
+        let mut config = ClashConfig {
+            mixed_port: self.mixed_port().unwrap_or(CLASH_MIXED_PORT),
+            ..Default::default()
+        };
+
+        // What rules and groups call each outbound
+        let mut names: HashMap<&str, String> = HashMap::new();
+        let mut proxies = Vec::new();
+        for (i, out) in self.outbounds().iter().enumerate() {
+            match out {
+                Outbound::Direct(_) => {
+                    names.insert(out.tag(), "DIRECT".to_string());
+                }
+                Outbound::Dns(_) => {}
+                Outbound::Selector(_) | Outbound::UrlTest(_) => {
+                    names.insert(out.tag(), out.tag().to_string());
+                }
+                _ => match out.to_link().and_then(|link| link_to_proxy(&link, out.tag())) {
+                    Ok(proxy) => {
+                        config.proxies.push(proxy);
+                        names.insert(out.tag(), out.tag().to_string());
+                        proxies.push(out.tag().to_string());
+                    }
+                    Err(reason) => unmapped.push(Unmapped { path: format!("outbounds[{}]", i), reason: reason.to_string() }),
+                },
+            }
+        }
+
+        let mut groups = Vec::new();
+        for (i, out) in self.outbounds().iter().enumerate() {
+            let group = match out {
+                // mihomo starts a select group on its first member
+                Outbound::Selector(selector) => ClashGroup {
+                    name: selector.tag.clone(),
+                    kind: "select".to_string(),
+                    proxies: selector.default.iter()
+                        .chain(selector.outbounds.iter().filter(|tag| Some(*tag) != selector.default.as_ref()))
+                        .cloned()
+                        .collect(),
+                    url: None,
+                    interval: None,
+                    tolerance: None,
+                },
+                Outbound::UrlTest(urltest) => {
+                    let interval = urltest.interval.as_deref().and_then(|interval| {
+                        let seconds = duration_seconds(interval);
+                        if seconds.is_none() {
+                            unmapped.push(Unmapped {
+                                path: format!("outbounds[{}].interval", i),
+                                reason: format!("{:?} is not a duration", interval),
+                            });
+                        }
+                        seconds
+                    });
+                    ClashGroup {
+                        name: urltest.tag.clone(),
+                        kind: "url-test".to_string(),
+                        proxies: urltest.outbounds.clone(),
+                        url: urltest.url.clone(),
+                        interval,
+                        tolerance: urltest.tolerance,
+                    }
+                }
+                _ => continue,
+            };
+            groups.push((i, group));
+        }
+        // mihomo refuses empty groups, and dropping one can empty the groups offering it
+        let empty = |names: &HashMap<&str, String>, group: &ClashGroup| !group.proxies.iter().any(|tag| names.contains_key(tag.as_str()));
+        while let Some(j) = groups.iter().position(|(_, group)| empty(&names, group)) {
+            let (i, group) = groups.remove(j);
+            names.remove(group.name.as_str());
+            unmapped.push(Unmapped { path: format!("outbounds[{}]", i), reason: "none of its outbounds are in the profile".to_string() });
+        }
+        for (_, mut group) in groups {
+            group.proxies = group.proxies.iter().filter_map(|tag| names.get(tag.as_str()).cloned()).collect();
+            config.proxy_groups.push(group);
+        }
+
+        // sing-box falls back to the first outbound
+        let final_tag = self.default_outbound();
+        let mut final_name = final_tag.and_then(|tag| names.get(tag).cloned());
+        if config.proxy_groups.is_empty() && !proxies.is_empty() {
+            if let Some(i) = proxies.iter().position(|proxy| Some(proxy) == final_name.as_ref()) {
+                let first = proxies.remove(i);
+                proxies.insert(0, first);
+                final_name = Some(CLASH_GROUP_NAME.to_string());
+            }
+            config.proxy_groups.push(ClashGroup {
+                name: CLASH_GROUP_NAME.to_string(),
+                kind: "select".to_string(),
+                proxies,
+                url: None,
+                interval: None,
+                tolerance: None,
+            });
+        }
+
+        for (i, rule) in self.route_rules().iter().enumerate() {
+            let path = format!("route.rules[{}]", i);
+            // mihomo answers DNS with its own resolver
+            if rule.protocol.as_deref() == Some("dns") || rule.action.as_deref() == Some("hijack-dns") {
+                continue;
+            }
+            let mut skip = |field: &str, reason: String| unmapped.push(Unmapped { path: format!("{}.{}", path, field), reason });
+
+            let target = match (rule.action.as_deref(), &rule.outbound) {
+                (Some("reject"), _) => "REJECT".to_string(),
+                (None | Some("route"), Some(outbound)) => match names.get(outbound.as_str()) {
+                    Some(name) => name.clone(),
+                    None => {
+                        skip("outbound", format!("{:?} is not in the profile", outbound));
+                        continue;
+                    }
+                },
+                (action, _) => {
+                    skip("action", format!("unsupported action {:?}", action.unwrap_or_default()));
+                    continue;
+                }
+            };
+
+            // Leaving out a condition would widen the rule, so the whole rule goes
+            let mut mappable = true;
+            if let Some(protocol) = &rule.protocol {
+                skip("protocol", format!("mihomo cannot match {} traffic", protocol));
+                mappable = false;
+            }
+            if rule.inbound.is_some() {
+                skip("inbound", "the sing-box inbounds are not in the profile".to_string());
+                mappable = false;
+            }
+            if rule.ip_is_private == Some(false) {
+                skip("ip_is_private", "mihomo cannot match only public addresses".to_string());
+                mappable = false;
+            }
+            for key in rule.extra.keys() {
+                skip(key, "no mihomo equivalent".to_string());
+                mappable = false;
+            }
+            if !mappable {
+                continue;
+            }
+
+            let mut destination: Vec<String> = Vec::new();
+            for suffix in rule.domain_suffix.iter().flatten() {
+                destination.push(format!("DOMAIN-SUFFIX,{}", suffix));
+            }
+            for cidr in rule.ip_cidr.iter().flatten() {
+                destination.push(format!("IP-CIDR,{}", cidr));
+            }
+            if rule.ip_is_private == Some(true) {
+                destination.extend(PRIVATE_CIDRS.iter().map(|cidr| format!("IP-CIDR,{}", cidr)));
+            }
+            let conditions: Vec<Vec<String>> = [
+                destination,
+                rule.port.iter().map(|port| format!("DST-PORT,{}", port)).collect(),
+                rule.process_name.iter().flatten().map(|process| format!("PROCESS-NAME,{}", process)).collect(),
+            ].into_iter().filter(|group| !group.is_empty()).collect();
+
+            match conditions.as_slice() {
+                [] => skip("outbound", "a rule without conditions mihomo can match".to_string()),
+                [alternatives] => {
+                    for condition in alternatives {
+                        // Like sing-box, an IP rule doesn't resolve domains to match
+                        let option = if condition.starts_with("IP-CIDR,") { ",no-resolve" } else { "" };
+                        config.rules.push(format!("{},{}{}", condition, target, option));
+                    }
+                }
+                groups => {
+                    let groups: Vec<String> = groups.iter()
+                        .map(|alternatives| match alternatives.as_slice() {
+                            [condition] => format!("({})", condition),
+                            _ => {
+                                let alternatives: Vec<String> = alternatives.iter().map(|condition| format!("({})", condition)).collect();
+                                format!("(OR,({}))", alternatives.join(","))
+                            }
+                        })
+                        .collect();
+                    config.rules.push(format!("AND,({}),{}", groups.join(","), target));
+                }
+            }
+        }
+
+        match final_name {
+            Some(name) => config.rules.push(format!("MATCH,{}", name)),
+            None => unmapped.push(Unmapped {
+                path: "route.final".to_string(),
+                reason: format!("{:?} is not in the profile", final_tag.unwrap_or_default()),
+            }),
+        }
+
+        ClashExport { config, unmapped }
+    }
+}
@@ -0,0 +1,498 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_yaml::{Mapping, Value as YamlValue};
+
+use crate::error::Error;
+use crate::models::Unmapped;
+use crate::subscription::ImportReport;
+use crate::url_parser::{decode_base64, Host, ShareLink};
+
+mod export;
+
+
+/// The parts of a Clash / mihomo profile that describe outbounds
+#[derive(Deserialize, Debug, Default)]
+pub struct ClashProfile {
+    #[serde(default)]
+    pub proxies: Vec<Map<String, Value>>,
+    #[serde(default, rename = "proxy-groups")]
+    pub proxy_groups: Vec<ClashGroup>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClashGroup {
+    pub name: String,
+    /// `select`, `url-test`, `fallback`, `load-balance` or `relay`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Names of proxies, other groups, `DIRECT` or `REJECT`
+    #[serde(default)]
+    pub proxies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Seconds between probes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<u32>,
+}
+
+/// A `proxy-groups` entry that was not translated
+#[derive(Debug)]
+pub struct SkippedGroup {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ClashImport {
+    /// Outcome of the `proxies` list; `line` is the 1-based position in the list
+    pub proxies: ImportReport,
+    /// Tags of the selector and urltest outbounds made from `proxy-groups`
+    pub groups: Vec<String>,
+    pub skipped_groups: Vec<SkippedGroup>,
+}
+
+/// Name of the `select` group a rendered profile gets when the config has no groups
+pub const CLASH_GROUP_NAME: &str = "PROXY";
+
+/// A mihomo profile; proxies keep their keys in the order mihomo's docs list them
+#[derive(Serialize, Debug, Default)]
+pub struct ClashConfig {
+    #[serde(rename = "mixed-port")]
+    pub mixed_port: u16,
+    pub proxies: Vec<Mapping>,
+    #[serde(rename = "proxy-groups")]
+    pub proxy_groups: Vec<ClashGroup>,
+    /// `TYPE,payload,target[,option]` lines, matched in order
+    pub rules: Vec<String>,
+}
+
+/// A mihomo profile rendered from a sing-box config
+#[derive(Debug)]
+pub struct ClashExport {
+    pub config: ClashConfig,
+    pub unmapped: Vec<Unmapped>,
+}
+
+pub fn parse_clash(yaml: &str) -> Result<ClashProfile, Error> {
+    serde_yaml::from_str(yaml).map_err(Error::ClashProfile)
+}
+
+/// Field of a proxy as a string; profiles write ports and flags both as strings and as scalars
+fn field(map: &Map<String, Value>, key: &str) -> Option<String> {
+    match map.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn flag(map: &Map<String, Value>, key: &str) -> bool {
+    matches!(field(map, key).as_deref(), Some("true" | "1"))
+}
+
+fn object<'a>(map: &'a Map<String, Value>, key: &str) -> Option<&'a Map<String, Value>> {
+    map.get(key)?.as_object()
+}
+
+/// A scalar or a list of scalars, e.g. `alpn` or the `host` of `h2-opts`
+fn list(map: &Map<String, Value>, key: &str) -> Vec<String> {
+    match map.get(key) {
+        Some(Value::Array(items)) => items.iter()
+            .filter_map(|item| match item {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Some(_) => field(map, key).into_iter().collect(),
+        None => Vec::new(),
+    }
+}
+
+/// `name` of a proxy, used as its tag
+pub fn proxy_name(proxy: &Map<String, Value>) -> Option<String> {
+    field(proxy, "name")
+}
+
+/// Translates a Clash proxy into the share link parameters it corresponds to,
+/// so the outbound is built by the same code as for links
+pub fn proxy_to_link(proxy: &Map<String, Value>) -> Result<ShareLink, Error> {
+    let kind = field(proxy, "type").ok_or_else(|| Error::invalid_link("type", "clash proxies must have a type"))?;
+    if !matches!(kind.as_str(), "vless" | "vmess" | "trojan" | "ss" | "hysteria2" | "tuic") {
+        return Err(Error::UnsupportedProtocol(kind));
+    }
+    let host: Host = field(proxy, "server")
+        .ok_or_else(|| Error::invalid_link("server", "clash proxies must have a server"))?
+        .parse()?;
+    let port = match field(proxy, "port") {
+        Some(port) => Some(port.parse::<u16>()
+            .map_err(|_| Error::invalid_link("port", format!("invalid port {:?}", port)))?),
+        None => None,
+    };
+
+    let user_info = match kind.as_str() {
+        "vless" | "vmess" => field(proxy, "uuid"),
+        "trojan" | "hysteria2" => field(proxy, "password"),
+        "ss" => field(proxy, "cipher")
+            .map(|cipher| format!("{}:{}", cipher, field(proxy, "password").unwrap_or_default())),
+        _ => field(proxy, "uuid")
+            .map(|uuid| format!("{}:{}", uuid, field(proxy, "password").unwrap_or_default())),
+    };
+
+    let mut query = Vec::new();
+    let mut push = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            query.push((key.to_string(), value));
+        }
+    };
+
+    match kind.as_str() {
+        "vless" | "vmess" => {
+            let security = match object(proxy, "reality-opts") {
+                Some(reality) if kind == "vless" => {
+                    push("pbk", field(reality, "public-key"));
+                    push("sid", field(reality, "short-id"));
+                    "reality"
+                }
+                _ if flag(proxy, "tls") => "tls",
+                _ => "none",
+            };
+            push("security", Some(security.to_string()));
+        }
+        "ss" => push("plugin", shadowsocks_plugin(proxy)),
+        _ => {}
+    }
+    push("sni", field(proxy, "servername").or_else(|| field(proxy, "sni")));
+    let alpn = list(proxy, "alpn");
+    push("alpn", Some(alpn.join(",")).filter(|alpn| !alpn.is_empty()));
+    push("fp", field(proxy, "client-fingerprint"));
+    if flag(proxy, "skip-cert-verify") {
+        push("allowInsecure", Some("1".to_string()));
+    }
+
+    match kind.as_str() {
+        "vless" => {
+            push("flow", field(proxy, "flow"));
+            push("packetEncoding", field(proxy, "packet-encoding"));
+        }
+        "vmess" => {
+            push("encryption", field(proxy, "cipher"));
+            push("aid", field(proxy, "alterId"));
+        }
+        "hysteria2" => {
+            push("mport", field(proxy, "ports"));
+            push("obfs", field(proxy, "obfs"));
+            push("obfs-password", field(proxy, "obfs-password"));
+            // mihomo's `fingerprint` is the certificate hash, not a uTLS fingerprint
+            push("pinSHA256", field(proxy, "fingerprint"));
+        }
+        "tuic" => {
+            push("congestion_control", field(proxy, "congestion-controller"));
+            push("udp_relay_mode", field(proxy, "udp-relay-mode"));
+        }
+        _ => {}
+    }
+
+    if matches!(kind.as_str(), "vless" | "vmess" | "trojan") {
+        transport_params(proxy, &mut push);
+    }
+
+    Ok(ShareLink {
+        scheme: kind,
+        user_info,
+        host,
+        port,
+        path: String::new(),
+        query,
+        fragment: proxy_name(proxy),
+    })
+}
+
+/// Maps `network` and its `*-opts` block onto `type`, `path`, `host`, `serviceName`,
+/// `headerType` and `ed`
+fn transport_params(proxy: &Map<String, Value>, push: &mut impl FnMut(&str, Option<String>)) {
+    let empty = Map::new();
+    let network = field(proxy, "network").unwrap_or_else(|| "tcp".to_string());
+    match network.as_str() {
+        "ws" => {
+            let opts = object(proxy, "ws-opts").unwrap_or(&empty);
+            let upgrade = flag(opts, "v2ray-http-upgrade");
+            push("type", Some(if upgrade { "httpupgrade" } else { "ws" }.to_string()));
+            push("path", field(opts, "path"));
+            push("host", object(opts, "headers").and_then(|headers| field(headers, "Host")));
+            if !upgrade {
+                push("ed", field(opts, "max-early-data"));
+            }
+        }
+        "grpc" => {
+            let opts = object(proxy, "grpc-opts").unwrap_or(&empty);
+            push("type", Some("grpc".to_string()));
+            push("serviceName", field(opts, "grpc-service-name"));
+        }
+        "h2" => {
+            let opts = object(proxy, "h2-opts").unwrap_or(&empty);
+            let host = list(opts, "host");
+            push("type", Some("http".to_string()));
+            push("path", field(opts, "path"));
+            push("host", Some(host.join(",")).filter(|host| !host.is_empty()));
+        }
+        // Clash's `http` network is HTTP header obfuscation over raw tcp
+        "http" => {
+            let opts = object(proxy, "http-opts").unwrap_or(&empty);
+            push("type", Some("tcp".to_string()));
+            push("headerType", Some("http".to_string()));
+            push("path", list(opts, "path").into_iter().next());
+            push("host", object(opts, "headers").and_then(|headers| list(headers, "Host").into_iter().next()));
+        }
+        other => push("type", Some(other.to_string())),
+    }
+}
+
+/// SIP003 `plugin` parameter for the `plugin` and `plugin-opts` of a Clash ss proxy
+fn shadowsocks_plugin(proxy: &Map<String, Value>) -> Option<String> {
+    let empty = Map::new();
+    let plugin = field(proxy, "plugin")?;
+    let opts = object(proxy, "plugin-opts").unwrap_or(&empty);
+    let mut parts = Vec::new();
+    match plugin.as_str() {
+        "obfs" => {
+            parts.push("obfs-local".to_string());
+            if let Some(mode) = field(opts, "mode") {
+                parts.push(format!("obfs={}", mode));
+            }
+            if let Some(host) = field(opts, "host") {
+                parts.push(format!("obfs-host={}", host));
+            }
+        }
+        "v2ray-plugin" => {
+            parts.push("v2ray-plugin".to_string());
+            if let Some(mode) = field(opts, "mode") {
+                parts.push(format!("mode={}", mode));
+            }
+            if flag(opts, "tls") {
+                parts.push("tls".to_string());
+            }
+            if let Some(host) = field(opts, "host") {
+                parts.push(format!("host={}", host));
+            }
+            if let Some(path) = field(opts, "path") {
+                parts.push(format!("path={}", path));
+            }
+        }
+        other => parts.push(other.to_string()),
+    }
+    Some(parts.join(";"))
+}
+
+fn insert(map: &mut Mapping, key: &str, value: impl Into<YamlValue>) {
+    map.insert(key.into(), value.into());
+}
+
+fn insert_some(map: &mut Mapping, key: &str, value: Option<&str>) {
+    if let Some(value) = value.filter(|value| !value.is_empty()) {
+        insert(map, key, value);
+    }
+}
+
+/// Translates the share link of an outbound into a Clash proxy named `name`, the
+/// inverse of [`proxy_to_link`]
+pub fn link_to_proxy(link: &ShareLink, name: &str) -> Result<Mapping, Error> {
+    let kind = match link.scheme.as_str() {
+        kind @ ("vless" | "vmess" | "trojan" | "ss" | "tuic") => kind,
+        "hysteria2" | "hy2" => "hysteria2",
+        other => return Err(Error::UnsupportedProtocol(other.to_string())),
+    };
+    let port = link.port.ok_or_else(|| Error::invalid_link("port", "a port is required"))?;
+    let user_info = link.user_info.clone().unwrap_or_default();
+
+    let mut proxy = Mapping::new();
+    insert(&mut proxy, "name", name);
+    insert(&mut proxy, "type", kind);
+    insert(&mut proxy, "server", link.host.to_string());
+    insert(&mut proxy, "port", port);
+    match kind {
+        "vless" | "vmess" => insert(&mut proxy, "uuid", user_info),
+        "ss" => {
+            let user_info = if user_info.contains(':') {
+                user_info
+            } else {
+                decode_base64(&user_info)
+                    .and_then(|decoded| String::from_utf8(decoded).ok())
+                    .ok_or_else(|| Error::invalid_link("userinfo", "expected method:password or its base64"))?
+            };
+            let (cipher, password) = user_info.split_once(':')
+                .ok_or_else(|| Error::invalid_link("userinfo", "expected method:password"))?;
+            insert(&mut proxy, "cipher", cipher);
+            insert(&mut proxy, "password", password);
+        }
+        "tuic" => {
+            let (uuid, password) = user_info.split_once(':').unwrap_or((&user_info, ""));
+            insert(&mut proxy, "uuid", uuid);
+            insert(&mut proxy, "password", password);
+        }
+        _ => insert(&mut proxy, "password", user_info),
+    }
+    insert(&mut proxy, "udp", true);
+
+    let security = link.param("security").unwrap_or(match kind {
+        "vless" | "vmess" | "ss" => "none",
+        _ => "tls",
+    });
+    if matches!(kind, "vless" | "vmess") && security != "none" {
+        insert(&mut proxy, "tls", true);
+    }
+    if security != "none" {
+        // mihomo names the SNI `servername` for the V2Ray protocols
+        let sni_key = if matches!(kind, "vless" | "vmess") { "servername" } else { "sni" };
+        insert_some(&mut proxy, sni_key, link.param("sni"));
+        let alpn: Vec<&str> = link.param("alpn").unwrap_or_default().split(',').filter(|alpn| !alpn.is_empty()).collect();
+        if !alpn.is_empty() {
+            insert(&mut proxy, "alpn", alpn);
+        }
+        insert_some(&mut proxy, "client-fingerprint", link.param("fp"));
+        if link.params("allowInsecure").into_iter().chain(link.params("allow_insecure")).chain(link.params("insecure"))
+            .any(|insecure| matches!(insecure, "1" | "true")) {
+            insert(&mut proxy, "skip-cert-verify", true);
+        }
+    }
+    if security == "reality" {
+        let mut reality = Mapping::new();
+        insert_some(&mut reality, "public-key", link.param("pbk"));
+        insert_some(&mut reality, "short-id", link.param("sid"));
+        insert(&mut proxy, "reality-opts", reality);
+    }
+
+    match kind {
+        "vless" => {
+            insert_some(&mut proxy, "flow", link.param("flow"));
+            insert_some(&mut proxy, "packet-encoding", link.param("packetEncoding"));
+        }
+        "vmess" => {
+            let alter_id: u32 = link.param("aid").and_then(|aid| aid.parse().ok()).unwrap_or(0);
+            insert(&mut proxy, "alterId", alter_id);
+            insert(&mut proxy, "cipher", link.param("encryption").unwrap_or("auto"));
+        }
+        "ss" => {
+            if let Some(plugin) = link.param("plugin") {
+                proxy_plugin(&mut proxy, plugin)?;
+            }
+        }
+        "hysteria2" => {
+            insert_some(&mut proxy, "ports", link.param("mport"));
+            insert_some(&mut proxy, "obfs", link.param("obfs"));
+            insert_some(&mut proxy, "obfs-password", link.param("obfs-password"));
+        }
+        "tuic" => {
+            insert_some(&mut proxy, "congestion-controller", link.param("congestion_control"));
+            insert_some(&mut proxy, "udp-relay-mode", link.param("udp_relay_mode"));
+        }
+        _ => {}
+    }
+
+    if matches!(kind, "vless" | "vmess" | "trojan") {
+        proxy_transport(&mut proxy, link, security != "none")?;
+    }
+    Ok(proxy)
+}
+
+/// Maps `type` and its parameters onto `network` and its `*-opts` block. sing-box's
+/// http transport is HTTP/2 over tls and HTTP/1.1 header obfuscation without it.
+fn proxy_transport(proxy: &mut Mapping, link: &ShareLink, tls: bool) -> Result<(), Error> {
+    let path = link.param("path").filter(|path| !path.is_empty());
+    let host = link.param("host").filter(|host| !host.is_empty());
+    let mut opts = Mapping::new();
+    let network = match link.param("type").unwrap_or("tcp") {
+        "tcp" if link.param("headerType") == Some("http") => "http",
+        "tcp" => return Ok(()),
+        "ws" => {
+            insert_some(&mut opts, "path", path);
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                insert(&mut headers, "Host", host);
+                insert(&mut opts, "headers", headers);
+            }
+            if let Some(ed) = link.param("ed").and_then(|ed| ed.parse::<u32>().ok()) {
+                insert(&mut opts, "max-early-data", ed);
+                insert(&mut opts, "early-data-header-name", "Sec-WebSocket-Protocol");
+            }
+            "ws"
+        }
+        // mihomo speaks HTTPUpgrade as a flavour of ws
+        "httpupgrade" => {
+            insert_some(&mut opts, "path", path);
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                insert(&mut headers, "Host", host);
+                insert(&mut opts, "headers", headers);
+            }
+            insert(&mut opts, "v2ray-http-upgrade", true);
+            "ws"
+        }
+        "grpc" => {
+            insert_some(&mut opts, "grpc-service-name", link.param("serviceName"));
+            "grpc"
+        }
+        "http" if tls => {
+            if let Some(host) = host {
+                insert(&mut opts, "host", host.split(',').collect::<Vec<&str>>());
+            }
+            insert_some(&mut opts, "path", path);
+            "h2"
+        }
+        "http" => "http",
+        other => return Err(Error::UnsupportedTransport(other.to_string())),
+    };
+    if network == "http" {
+        insert(&mut opts, "method", "GET");
+        insert(&mut opts, "path", vec![path.unwrap_or("/")]);
+        if let Some(host) = host {
+            let mut headers = Mapping::new();
+            insert(&mut headers, "Host", host.split(',').collect::<Vec<&str>>());
+            insert(&mut opts, "headers", headers);
+        }
+    }
+    insert(proxy, "network", network);
+    if !opts.is_empty() {
+        insert(proxy, &format!("{}-opts", network), opts);
+    }
+    Ok(())
+}
+
+/// `plugin` and `plugin-opts` of a Clash ss proxy for a SIP003 `plugin` parameter
+fn proxy_plugin(proxy: &mut Mapping, plugin: &str) -> Result<(), Error> {
+    let mut parts = plugin.split(';');
+    let name = parts.next().unwrap_or_default();
+    let mut opts = Mapping::new();
+    let name = match name {
+        "obfs-local" | "simple-obfs" => {
+            for (key, value) in parts.filter_map(|part| part.split_once('=')) {
+                match key {
+                    "obfs" => insert(&mut opts, "mode", value),
+                    "obfs-host" => insert(&mut opts, "host", value),
+                    _ => {}
+                }
+            }
+            "obfs"
+        }
+        "v2ray-plugin" => {
+            for part in parts {
+                match part.split_once('=') {
+                    Some(("mode", value)) => insert(&mut opts, "mode", value),
+                    Some(("host", value)) => insert(&mut opts, "host", value),
+                    Some(("path", value)) => insert(&mut opts, "path", value),
+                    None if part == "tls" => insert(&mut opts, "tls", true),
+                    _ => {}
+                }
+            }
+            "v2ray-plugin"
+        }
+        other => return Err(Error::invalid_link("plugin", format!("unsupported plugin {:?}", other))),
+    };
+    insert(proxy, "plugin", name);
+    insert(proxy, "plugin-opts", opts);
+    Ok(())
+}
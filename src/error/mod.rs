@@ -0,0 +1,162 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::url_parser::{redact_link, ParseError};
+
+
+pub enum Error {
+    /// A share link or one of its components could not be parsed
+    Parse { input: String, kind: ParseError },
+    /// The share link scheme has no outbound mapping
+    UnsupportedProtocol(String),
+    /// The `security` parameter of a share link is not supported
+    UnsupportedSecurity(String),
+    /// The `type` (transport) parameter of a share link is not supported
+    UnsupportedTransport(String),
+    /// A share link parsed but a field the outbound needs is missing or invalid
+    InvalidLink { field: String, reason: String },
+    /// The sing-box template could not be read
+    TemplateIo { path: PathBuf, source: io::Error },
+    /// The sing-box template is not valid JSON or does not match the models
+    TemplateSchema { path: PathBuf, source: serde_json::Error },
+    /// A strictly read config has a missing or ill-typed field
+    InvalidField { field: String, reason: String },
+    /// The config has no outbound of the required type
+    MissingOutbound(String),
+    /// None of the links of a subscription could be imported
+    EmptySubscription,
+    /// A subscription body is in none of the supported formats
+    InvalidSubscription(String),
+    /// An imported server is already in the config under another outbound
+    DuplicateServer { server: String, port: i32, tag: String },
+    /// A Clash profile is not valid YAML or does not have the expected shape
+    ClashProfile(serde_yaml::Error),
+    /// An Xray config is not valid JSON or does not have the expected shape
+    XrayConfig(serde_json::Error),
+    /// The data does not fit in a QR code
+    QrEncode(qrcode::types::QrError),
+    /// A QR code image could not be read or written
+    Image { path: PathBuf, source: image::ImageError },
+    /// The image file extension is not one of the supported formats
+    UnsupportedImageFormat(PathBuf),
+    /// No readable QR code was found in an image
+    NoQrCode(PathBuf),
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl Error {
+    pub fn invalid_link(field: &str, reason: impl Into<String>) -> Self {
+        Error::InvalidLink { field: field.to_string(), reason: reason.into() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // Links carry the UUID or password in their user-info or query
+            Error::Parse { input, kind } => write!(f, "failed to parse {:?}: {}", redact_link(input), kind),
+            Error::UnsupportedProtocol(p) => write!(f, "unsupported protocol: {:?}", p),
+            Error::UnsupportedSecurity(s) => write!(f, "unsupported security: {:?}", s),
+            Error::UnsupportedTransport(t) => write!(f, "unsupported transport: {:?}", t),
+            Error::InvalidLink { field, reason } => write!(f, "invalid share link field {:?}: {}", field, reason),
+            Error::TemplateIo { path, source } => write!(f, "failed to read template {}: {}", path.display(), source),
+            Error::TemplateSchema { path, source } => write!(f, "invalid template {}: {}", path.display(), source),
+            Error::InvalidField { field, reason } if field.is_empty() => write!(f, "invalid config: {}", reason),
+            Error::InvalidField { field, reason } => write!(f, "invalid config field {}: {}", field, reason),
+            Error::MissingOutbound(t) => write!(f, "config has no {} outbound", t),
+            Error::EmptySubscription => write!(f, "subscription has no usable links"),
+            Error::InvalidSubscription(reason) => write!(f, "invalid subscription: {}", reason),
+            Error::DuplicateServer { server, port, tag } => write!(f, "{}:{} is already used by outbound {:?}", server, port, tag),
+            Error::ClashProfile(e) => write!(f, "invalid Clash profile: {}", e),
+            Error::XrayConfig(e) => write!(f, "invalid Xray config: {}", e),
+            Error::QrEncode(e) => write!(f, "failed to encode QR code: {}", e),
+            Error::Image { path, source } => write!(f, "failed to process image {}: {}", path.display(), source),
+            Error::UnsupportedImageFormat(path) => write!(f, "unsupported image format: {}", path.display()),
+            Error::NoQrCode(path) => write!(f, "no QR code found in {}", path.display()),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+// Written out rather than derived so that `{:?}` does not print credentials either
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse { input, kind } => f.debug_struct("Parse")
+                .field("input", &redact_link(input))
+                .field("kind", kind)
+                .finish(),
+            Error::UnsupportedProtocol(p) => f.debug_tuple("UnsupportedProtocol").field(p).finish(),
+            Error::UnsupportedSecurity(s) => f.debug_tuple("UnsupportedSecurity").field(s).finish(),
+            Error::UnsupportedTransport(t) => f.debug_tuple("UnsupportedTransport").field(t).finish(),
+            Error::InvalidLink { field, reason } => f.debug_struct("InvalidLink")
+                .field("field", field)
+                .field("reason", reason)
+                .finish(),
+            Error::TemplateIo { path, source } => f.debug_struct("TemplateIo")
+                .field("path", path)
+                .field("source", source)
+                .finish(),
+            Error::TemplateSchema { path, source } => f.debug_struct("TemplateSchema")
+                .field("path", path)
+                .field("source", source)
+                .finish(),
+            Error::InvalidField { field, reason } => f.debug_struct("InvalidField")
+                .field("field", field)
+                .field("reason", reason)
+                .finish(),
+            Error::MissingOutbound(t) => f.debug_tuple("MissingOutbound").field(t).finish(),
+            Error::EmptySubscription => write!(f, "EmptySubscription"),
+            Error::InvalidSubscription(reason) => f.debug_tuple("InvalidSubscription").field(reason).finish(),
+            Error::DuplicateServer { server, port, tag } => f.debug_struct("DuplicateServer")
+                .field("server", server)
+                .field("port", port)
+                .field("tag", tag)
+                .finish(),
+            Error::ClashProfile(e) => f.debug_tuple("ClashProfile").field(e).finish(),
+            Error::XrayConfig(e) => f.debug_tuple("XrayConfig").field(e).finish(),
+            Error::QrEncode(e) => f.debug_tuple("QrEncode").field(e).finish(),
+            Error::Image { path, source } => f.debug_struct("Image")
+                .field("path", path)
+                .field("source", source)
+                .finish(),
+            Error::UnsupportedImageFormat(path) => f.debug_tuple("UnsupportedImageFormat").field(path).finish(),
+            Error::NoQrCode(path) => f.debug_tuple("NoQrCode").field(path).finish(),
+            Error::Io(e) => f.debug_tuple("Io").field(e).finish(),
+            Error::Json(e) => f.debug_tuple("Json").field(e).finish(),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse { kind, .. } => Some(kind),
+            Error::TemplateIo { source, .. } => Some(source),
+            Error::TemplateSchema { source, .. } => Some(source),
+            Error::ClashProfile(e) => Some(e),
+            Error::XrayConfig(e) => Some(e),
+            Error::QrEncode(e) => Some(e),
+            Error::Image { source, .. } => Some(source),
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
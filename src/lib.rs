@@ -1,6 +1,12 @@
+pub mod clash;
+pub mod error;
 pub mod models;
+pub mod qr;
+pub mod subscription;
 pub mod url_parser;
 pub mod utils;
+pub mod xray;
 
+pub use error::Error;
 pub use models::*;
 pub use url_parser::*;
@@ -1,47 +1,686 @@
-use lessvless::models::SingBoxConfig;
+use lessvless::clash::{parse_clash, ClashImport};
+use lessvless::xray::{parse_xray, XrayImport};
+use lessvless::qr::{decode_image, render_terminal, write_image};
+use lessvless::url_parser::{format_remote_profile_link, parse_share_link};
+use lessvless::models::{Connection, GroupOptions, GroupStrategy, Outbound, RouteRule, Severity, SingBoxConfig};
+use lessvless::subscription::{decode_json_subscription, decode_subscription, is_json_subscription};
+use lessvless::Error;
+use serde_json::{json, Map, Value};
 use std::fs::File;
-use clap::Parser;
-use std::io::Write;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::process::ExitCode;
 
 
-#[derive(Parser)]
-struct Args {
+/// Exit status of a command that could not do its job; clap exits with 2 on a bad
+/// command line
+const EXIT_FAILURE: u8 = 1;
+/// Exit status of `validate` when the config has problems
+const EXIT_INVALID: u8 = 3;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Group {
+    None,
+    Selector,
+    Urltest,
+    Both,
+}
+
+impl From<Group> for GroupStrategy {
+    fn from(group: Group) -> Self {
+        match group {
+            Group::None => GroupStrategy::None,
+            Group::Selector => GroupStrategy::Selector,
+            Group::Urltest => GroupStrategy::UrlTest,
+            Group::Both => GroupStrategy::Both,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    SingBox,
+    Xray,
+    Clash,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// sing-box template
     #[clap(long = "config")]
     config: String,
 
+    /// Share link; a single link updates the template's outbound of its protocol,
+    /// several links replace the template's proxies like a subscription does
     #[clap(long = "url")]
-    url: Option<String>,
+    url: Vec<String>,
+
+    /// File with a subscription body, `-` for stdin: a base64 list of share links,
+    /// or SIP008 / sing-box JSON whose servers replace the template's proxies, or
+    /// join those of `--url` and `--from-qr`
+    #[clap(long = "subscription")]
+    subscription: Option<String>,
+
+    /// PNG or JPEG image whose QR codes hold share links, e.g. a screenshot of a
+    /// provider's panel; each code is read like a `--url`
+    #[clap(long = "from-qr")]
+    from_qr: Vec<String>,
+
+    /// Clash / mihomo YAML profile whose `proxies` replace the template's proxies,
+    /// `-` for stdin; its `proxy-groups` are kept unless `--group` is given
+    #[clap(long = "clash", conflicts_with_all = ["url", "subscription", "from_qr"])]
+    clash: Option<String>,
+
+    /// Xray-core `config.json` whose proxy outbounds replace the template's, `-` for
+    /// stdin; its balancers become urltests and its routing rules are appended
+    #[clap(long = "xray", conflicts_with_all = ["url", "subscription", "from_qr", "clash"])]
+    xray: Option<String>,
+
+    /// Outbound groups to put the servers in; defaults to `both` when several
+    /// servers are imported and `none` otherwise
+    #[clap(long = "group", value_enum)]
+    group: Option<Group>,
+
+    /// URL the urltest group probes
+    #[clap(long = "test-url")]
+    test_url: Option<String>,
+
+    /// Interval between urltest probes, e.g. `3m`
+    #[clap(long = "test-interval")]
+    test_interval: Option<String>,
+
+    /// Latency difference in milliseconds before urltest switches servers
+    #[clap(long = "tolerance")]
+    tolerance: Option<u32>,
 
     #[clap(long = "dns")]
     dns: Option<String>,
-    
+
     #[clap(long = "output")]
     output: Option<String>,
+
+    /// Core the generated config is for; `xray` renders the servers and routing of
+    /// the template with a socks/http inbound skeleton, `clash` a mihomo YAML profile
+    #[clap(long = "format", value_enum, default_value = "sing-box")]
+    format: Format,
 }
 
-fn main() -> std::io::Result<()> {
-    let args = Args::parse();
+#[derive(Args)]
+struct ExplainArgs {
+    #[clap(long = "config")]
+    config: String,
+
+    /// Inbound tag the connection arrives on
+    #[clap(long = "inbound")]
+    inbound: Option<String>,
+
+    /// Sniffed protocol, e.g. `tls`, `http`, `quic` or `dns`
+    #[clap(long = "protocol")]
+    protocol: Option<String>,
+
+    #[clap(long = "domain")]
+    domain: Option<String>,
+
+    #[clap(long = "ip")]
+    ip: Option<IpAddr>,
+
+    #[clap(long = "port")]
+    port: Option<u16>,
+
+    /// Name of the process opening the connection
+    #[clap(long = "process")]
+    process: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build a config from a template and share links, a subscription, QR codes,
+    /// or a Clash or Xray config
+    Generate(GenerateArgs),
+    /// Check that a sing-box config loads and that its tags, DNS servers, CIDRs and
+    /// ports are consistent
+    Validate {
+        #[clap(long = "config")]
+        config: String,
+    },
+    /// Look for DNS leaks and routing loops in a sing-box config
+    Lint {
+        #[clap(long = "config")]
+        config: String,
+    },
+    /// List the outbounds and route rules of a sing-box config
+    Inspect {
+        #[clap(long = "config")]
+        config: String,
+    },
+    /// Show which route rule and outbound a connection would get, and why earlier
+    /// rules don't match
+    Explain(ExplainArgs),
+    /// Translate a config from one core's format to another's
+    Convert {
+        /// Config to convert, `-` for stdin
+        input: String,
+
+        #[clap(long = "from", value_enum)]
+        from: Format,
+
+        #[clap(long = "to", value_enum)]
+        to: Format,
 
-    let default_config = SingBoxConfig::from_file(args.config).unwrap();
-    let mut new_config: SingBoxConfig = default_config.clone();
-    
-    if let Some(url) = args.url {
-        new_config.enrich_from_url(url).unwrap();
+        /// sing-box template that Xray and Clash configs are imported into
+        #[clap(long = "config", required_if_eq_any = [("from", "xray"), ("from", "clash")])]
+        config: Option<String>,
+
+        #[clap(long = "output")]
+        output: Option<String>,
+    },
+    /// Print the share link of every proxy outbound of a sing-box config
+    Export {
+        #[clap(long = "config")]
+        config: String,
+
+        /// Only export the outbounds with these tags
+        #[clap(long = "tag")]
+        tag: Vec<String>,
+
+        /// Print each link as a QR code as well
+        #[clap(long = "qr", conflicts_with = "json")]
+        qr: bool,
+    },
+    /// Show a share link, or a sing-box remote profile import link, as a QR code
+    Qr {
+        #[clap(required_unless_present = "remote_profile")]
+        link: Option<String>,
+
+        /// URL of a sing-box profile to encode as an `import-remote-profile` link
+        #[clap(long = "remote-profile", conflicts_with = "link")]
+        remote_profile: Option<String>,
+
+        /// Name the sing-box app gives the remote profile
+        #[clap(long = "name", requires = "remote_profile")]
+        name: Option<String>,
+
+        /// Write a PNG or SVG image instead of printing to the terminal
+        #[clap(long = "output")]
+        output: Option<String>,
+    },
+}
+
+#[derive(Parser)]
+#[command(after_help = "Exit status: 0 on success, 1 when the command fails, 2 on a bad command line, 3 when `validate` finds problems or `lint` finds errors.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Print the result, warnings and errors as one JSON object on stdout
+    #[clap(long = "json", global = true)]
+    json: bool,
+
+    /// Read configs the forgiving way: fill in placeholders for missing or ill-typed
+    /// outbound fields instead of failing
+    #[clap(long = "lenient", global = true)]
+    lenient: bool,
+}
+
+/// What a command prints
+struct Outcome {
+    /// Shown on the terminal; empty when the result went to a file
+    text: String,
+    /// Shown with `--json`
+    json: Map<String, Value>,
+    code: u8,
+}
+
+impl Outcome {
+    fn new(text: String, json: Value) -> Outcome {
+        let json = match json {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        Outcome { text, json, code: 0 }
     }
+}
+
+fn read_input(path: &str) -> Result<String, Error> {
+    let mut body = String::new();
+    if path == "-" {
+        std::io::stdin().read_to_string(&mut body)?;
+    } else {
+        File::open(path)?.read_to_string(&mut body)?;
+    }
+    Ok(body)
+}
+
+/// Reads a sing-box config, `-` for stdin
+fn load_config(path: &str, strict: bool) -> Result<SingBoxConfig, Error> {
+    match (path, strict) {
+        ("-", true) => SingBoxConfig::from_value_strict(serde_json::from_str(&read_input(path)?)?),
+        ("-", false) => Ok(serde_json::from_str(&read_input(path)?)?),
+        (_, true) => SingBoxConfig::from_file_strict(path),
+        (_, false) => SingBoxConfig::from_file(path),
+    }
+}
+
+/// Renders `config` for the core `format` names, as text and as JSON
+fn render(config: &SingBoxConfig, format: Format, warnings: &mut Vec<String>) -> Result<(String, Value), Error> {
+    match format {
+        Format::SingBox => Ok((serde_json::to_string_pretty(config)?, serde_json::to_value(config)?)),
+        Format::Xray => {
+            let xray = config.to_xray();
+            for unmapped in &xray.unmapped {
+                warnings.push(format!("left out of the Xray config: {}", unmapped));
+            }
+            Ok((serde_json::to_string_pretty(&xray.config)?, xray.config))
+        }
+        Format::Clash => {
+            let clash = config.to_clash();
+            for unmapped in &clash.unmapped {
+                warnings.push(format!("left out of the Clash profile: {}", unmapped));
+            }
+            let text = serde_yaml::to_string(&clash.config).map_err(Error::ClashProfile)?;
+            Ok((text, serde_json::to_value(&clash.config)?))
+        }
+    }
+}
+
+/// Writes a rendered config to `output`, or hands it back to be printed
+fn write_output(output: Option<&str>, text: String, value: Value) -> Result<Outcome, Error> {
+    match output {
+        Some(path) => {
+            let mut file = File::create(path)?;
+            file.write_all(text.as_bytes())?;
+            Ok(Outcome::new(String::new(), json!({ "output": path })))
+        }
+        None => Ok(Outcome::new(text, json!({ "config": value }))),
+    }
+}
+
+fn import_clash(config: &mut SingBoxConfig, path: &str, with_groups: bool, warnings: &mut Vec<String>) -> Result<ClashImport, Error> {
+    let import = config.import_clash(&parse_clash(&read_input(path)?)?, with_groups);
+    for skipped in &import.proxies.skipped {
+        warnings.push(format!("skipped proxy {} {:?}: {}", skipped.line, skipped.link, skipped.reason));
+    }
+    for dropped in &import.proxies.dropped {
+        warnings.push(format!("left out {} of proxy {}: {}", dropped.param, dropped.line, dropped.reason));
+    }
+    for skipped in &import.skipped_groups {
+        warnings.push(format!("skipped proxy group {:?}: {}", skipped.name, skipped.reason));
+    }
+    if import.proxies.imported.is_empty() {
+        return Err(Error::EmptySubscription);
+    }
+    Ok(import)
+}
+
+fn import_xray(config: &mut SingBoxConfig, path: &str, warnings: &mut Vec<String>) -> Result<XrayImport, Error> {
+    let import = config.import_xray(&parse_xray(&read_input(path)?)?);
+    for skipped in &import.outbounds.skipped {
+        warnings.push(format!("skipped outbound {} {:?}: {}", skipped.line, skipped.link, skipped.reason));
+    }
+    for unmapped in &import.unmapped {
+        warnings.push(format!("not imported from the Xray config: {}", unmapped));
+    }
+    if import.outbounds.imported.is_empty() {
+        return Err(Error::EmptySubscription);
+    }
+    Ok(import)
+}
+
+fn generate(args: GenerateArgs, strict: bool, warnings: &mut Vec<String>) -> Result<Outcome, Error> {
+    let mut new_config = load_config(&args.config, strict)?;
+
+    let mut links = args.url;
+    // Where each link came from, for the warnings about the ones that are skipped
+    let mut sources: Vec<String> = (1..=links.len()).map(|i| format!("--url {}", i)).collect();
+    for path in &args.from_qr {
+        let decoded = decode_image(path)?;
+        sources.extend(decoded.iter().map(|_| format!("QR code in {}", path)));
+        links.extend(decoded);
+    }
+    let mut json_subscription = None;
+    if let Some(path) = &args.subscription {
+        let body = read_input(path)?;
+        if is_json_subscription(&body) {
+            json_subscription = Some(body);
+        } else {
+            let decoded = decode_subscription(&body)?;
+            sources.extend((1..=decoded.len()).map(|i| format!("subscription link {}", i)));
+            links.extend(decoded);
+        }
+    }
+    let link_subscription = args.subscription.is_some() && json_subscription.is_none();
+    let has_links = !links.is_empty();
+
+    let mut group = args.group.unwrap_or(Group::None);
+    if let Some(path) = &args.clash {
+        let import = import_clash(&mut new_config, path, args.group.is_none(), warnings)?;
+        if import.groups.is_empty() && import.proxies.imported.len() > 1 {
+            group = args.group.unwrap_or(Group::Both);
+        }
+    } else if let Some(path) = &args.xray {
+        let import = import_xray(&mut new_config, path, warnings)?;
+        if import.groups.is_empty() && import.outbounds.imported.len() > 1 {
+            group = args.group.unwrap_or(Group::Both);
+        }
+    } else if links.len() == 1 && !link_subscription {
+        let url = links.remove(0);
+        new_config.enrich_from_url(url.clone())?;
+        for (param, reason) in Outbound::dropped_params(&parse_share_link(&url)?) {
+            warnings.push(format!("left out {} of {}: {}", param, sources[0], reason));
+        }
+    } else if !links.is_empty() || link_subscription {
+        let report = new_config.import_links(&links);
+        for skipped in &report.skipped {
+            warnings.push(format!("skipped {}: {}", sources[skipped.line - 1], skipped.reason));
+        }
+        for dropped in &report.dropped {
+            warnings.push(format!("left out {} of {}: {}", dropped.param, sources[dropped.line - 1], dropped.reason));
+        }
+        if report.imported.is_empty() {
+            return Err(Error::EmptySubscription);
+        }
+        if report.imported.len() > 1 {
+            group = args.group.unwrap_or(Group::Both);
+        }
+    }
+    // SIP008 and sing-box JSON subscriptions replace the template's proxies, or add
+    // their servers to those the links gave
+    if let Some(body) = &json_subscription {
+        let entries = decode_json_subscription(body)?;
+        let report = if has_links {
+            new_config.merge_outbounds(entries)
+        } else {
+            new_config.import_outbounds(entries)
+        };
+        for skipped in &report.skipped {
+            warnings.push(format!("skipped server {} {:?}: {}", skipped.line, skipped.link, skipped.reason));
+        }
+        if report.imported.is_empty() {
+            return Err(Error::EmptySubscription);
+        }
+        if new_config.outbounds().iter().filter(|out| out.is_proxy()).count() > 1 {
+            group = args.group.unwrap_or(Group::Both);
+        }
+    }
+    new_config.add_groups(&GroupOptions {
+        strategy: group.into(),
+        url: args.test_url,
+        interval: args.test_interval,
+        tolerance: args.tolerance,
+    });
     if let Some(dns) = args.dns {
-        new_config.enrich_from_dns(dns).unwrap();
+        new_config.enrich_from_dns(dns)?;
     }
 
-    let mut json_data = serde_json::to_string_pretty(&new_config)?;  
-    json_data = json_data.replace("\\\"", "");
-    
-    if let Some(output) = args.output {
-        let mut file = File::create(output)?;
-        file.write_all(json_data.as_bytes())?;
-    } else {
-        println!("{}", json_data);
+    let (text, value) = render(&new_config, args.format, warnings)?;
+    write_output(args.output.as_deref(), text, value)
+}
+
+fn validate(path: &str, strict: bool) -> Result<Outcome, Error> {
+    let config = match load_config(path, strict) {
+        Ok(config) => config,
+        Err(Error::TemplateSchema { source, .. }) => {
+            let diagnostic = json!({ "path": null, "line": source.line(), "column": source.column(), "message": source.to_string() });
+            let mut outcome = Outcome::new(format!("{}: {}", path, source), json!({ "valid": false, "diagnostics": [diagnostic] }));
+            outcome.code = EXIT_INVALID;
+            return Ok(outcome);
+        }
+        Err(Error::InvalidField { field, reason }) => {
+            let diagnostic = json!({ "path": field, "message": reason });
+            let mut outcome = Outcome::new(format!("{}: {}: {}", path, field, reason), json!({ "valid": false, "diagnostics": [diagnostic] }));
+            outcome.code = EXIT_INVALID;
+            return Ok(outcome);
+        }
+        Err(e) => return Err(e),
+    };
+    let diagnostics = config.validate();
+    if diagnostics.is_empty() {
+        return Ok(Outcome::new(format!("{}: ok", path), json!({ "valid": true, "diagnostics": [] })));
+    }
+    let lines: Vec<String> = diagnostics.iter().map(|diagnostic| format!("{}: {}", path, diagnostic)).collect();
+    let mut outcome = Outcome::new(lines.join("\n"), json!({ "valid": false, "diagnostics": diagnostics }));
+    outcome.code = EXIT_INVALID;
+    Ok(outcome)
+}
+
+fn lint(path: &str, strict: bool) -> Result<Outcome, Error> {
+    let findings = load_config(path, strict)?.lint();
+    if findings.is_empty() {
+        return Ok(Outcome::new(format!("{}: ok", path), json!({ "findings": [] })));
+    }
+    let lines: Vec<String> = findings.iter().map(|finding| format!("{}: {}", path, finding)).collect();
+    let mut outcome = Outcome::new(lines.join("\n"), json!({ "findings": findings }));
+    if findings.iter().any(|finding| finding.severity == Severity::Error) {
+        outcome.code = EXIT_INVALID;
+    }
+    Ok(outcome)
+}
+
+/// Lines up the cells of `rows` under each other
+fn table(rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            match widths.get_mut(i) {
+                Some(width) => *width = (*width).max(cell.chars().count()),
+                None => widths.push(cell.chars().count()),
+            }
+        }
+    }
+    let lines: Vec<String> = rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row.iter().enumerate()
+                .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Conditions of a route rule as `field=value` pairs
+fn rule_conditions(rule: &RouteRule) -> Result<Vec<String>, Error> {
+    let Value::Object(mut fields) = serde_json::to_value(rule)? else {
+        return Ok(Vec::new());
+    };
+    fields.remove("outbound");
+    fields.remove("action");
+    Ok(fields.into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Array(items) => items.iter()
+                    .map(|item| item.as_str().map(str::to_string).unwrap_or_else(|| item.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                other => other.to_string(),
+            };
+            format!("{}={}", key, value)
+        })
+        .collect())
+}
+
+fn explain(args: ExplainArgs, strict: bool) -> Result<Outcome, Error> {
+    let config = load_config(&args.config, strict)?;
+    let connection = Connection {
+        inbound: args.inbound,
+        protocol: args.protocol,
+        domain: args.domain,
+        ip: args.ip,
+        port: args.port,
+        process: args.process,
+    };
+    let explanation = config.explain(&connection);
+
+    let mut lines: Vec<String> = explanation.misses.iter()
+        .map(|miss| format!("route.rules[{}]: skipped: {}", miss.index, miss.reasons.join("; ")))
+        .collect();
+    match explanation.rule {
+        Some(index) => {
+            let conditions = rule_conditions(&config.route_rules()[index])?;
+            lines.push(format!("route.rules[{}]: matched: {}", index, conditions.join(" ")));
+        }
+        None => lines.push("route.final: no rule matched".to_string()),
+    }
+    match (explanation.action.as_str(), &explanation.outbound) {
+        ("route", Some(outbound)) => lines.push(format!("outbound: {}", outbound)),
+        (action, _) => lines.push(format!("action: {}", action)),
+    }
+    Ok(Outcome::new(lines.join("\n"), json!(explanation)))
+}
+
+fn inspect(config: &str, strict: bool) -> Result<Outcome, Error> {
+    let config = load_config(config, strict)?;
+
+    let mut outbounds = vec![vec!["TAG".to_string(), "TYPE".to_string(), "SERVER".to_string()]];
+    let mut outbounds_json = Vec::new();
+    for outbound in config.outbounds() {
+        let target = match (outbound.server(), outbound) {
+            (Some((server, port)), _) => format!("{}:{}", server, port),
+            (None, Outbound::Selector(_) | Outbound::UrlTest(_)) => outbound.members().join(", "),
+            _ => String::new(),
+        };
+        outbounds.push(vec![outbound.tag().to_string(), outbound.type_name().to_string(), target]);
+
+        let mut value = json!({ "tag": outbound.tag(), "type": outbound.type_name() });
+        if let Some((server, port)) = outbound.server() {
+            value["server"] = json!(server);
+            value["server_port"] = json!(port);
+        }
+        if matches!(outbound, Outbound::Selector(_) | Outbound::UrlTest(_)) {
+            value["outbounds"] = json!(outbound.members());
+        }
+        outbounds_json.push(value);
+    }
+
+    let mut rules = vec![vec!["#".to_string(), "MATCH".to_string(), "TARGET".to_string()]];
+    for (i, rule) in config.route_rules().iter().enumerate() {
+        let target = match (rule.action(), rule.outbound()) {
+            (None | Some("route"), Some(outbound)) => outbound.to_string(),
+            (Some(action), _) => action.to_string(),
+            (None, None) => String::new(),
+        };
+        rules.push(vec![i.to_string(), rule_conditions(rule)?.join(" "), target]);
+    }
+    rules.push(vec!["final".to_string(), String::new(), config.default_outbound().unwrap_or_default().to_string()]);
+
+    let text = format!("{}\n\n{}", table(&outbounds), table(&rules));
+    Ok(Outcome::new(text, json!({
+        "outbounds": outbounds_json,
+        "rules": config.route_rules(),
+        "final": config.default_outbound(),
+    })))
+}
+
+fn convert(input: &str, from: Format, to: Format, template: Option<&str>, output: Option<&str>, strict: bool, warnings: &mut Vec<String>) -> Result<Outcome, Error> {
+    let config = match (from, template) {
+        (Format::SingBox, _) => load_config(input, strict)?,
+        (_, None) => unreachable!("clap requires --config unless converting from sing-box"),
+        (Format::Xray, Some(template)) => {
+            let mut config = load_config(template, strict)?;
+            import_xray(&mut config, input, warnings)?;
+            config
+        }
+        (Format::Clash, Some(template)) => {
+            let mut config = load_config(template, strict)?;
+            import_clash(&mut config, input, true, warnings)?;
+            config
+        }
+    };
+    let (text, value) = render(&config, to, warnings)?;
+    write_output(output, text, value)
+}
+
+fn export(config: &str, tags: &[String], qr: bool, strict: bool) -> Result<Outcome, Error> {
+    let config = load_config(config, strict)?;
+    for tag in tags {
+        if !config.outbounds().iter().any(|out| out.tag() == tag) {
+            return Err(Error::MissingOutbound(format!("{:?}", tag)));
+        }
+    }
+    let mut lines = Vec::new();
+    let mut links = Vec::new();
+    for outbound in config.outbounds() {
+        if tags.is_empty() && !outbound.is_proxy() {
+            continue;
+        }
+        if tags.is_empty() || tags.iter().any(|tag| tag == outbound.tag()) {
+            let link = outbound.to_share_link()?;
+            lines.push(link.clone());
+            if qr {
+                lines.push(render_terminal(&link)?);
+            }
+            links.push(json!({ "tag": outbound.tag(), "link": link }));
+        }
+    }
+    Ok(Outcome::new(lines.join("\n"), json!({ "links": links })))
+}
+
+fn qr(link: Option<String>, remote_profile: Option<String>, name: Option<String>, output: Option<String>) -> Result<Outcome, Error> {
+    let data = match remote_profile {
+        Some(url) => format_remote_profile_link(&url, name.as_deref()),
+        None => link.unwrap_or_default(),
+    };
+    match output {
+        Some(path) => {
+            write_image(&data, &path)?;
+            Ok(Outcome::new(String::new(), json!({ "data": data, "output": path })))
+        }
+        None => {
+            let code = render_terminal(&data)?;
+            Ok(Outcome::new(code.clone(), json!({ "data": data, "qr": code })))
+        }
     }
-     
+}
 
-    Ok(())
+fn run(command: Command, strict: bool, warnings: &mut Vec<String>) -> Result<Outcome, Error> {
+    match command {
+        Command::Generate(args) => generate(args, strict, warnings),
+        Command::Validate { config } => validate(&config, strict),
+        Command::Lint { config } => lint(&config, strict),
+        Command::Inspect { config } => inspect(&config, strict),
+        Command::Explain(args) => explain(args, strict),
+        Command::Convert { input, from, to, config, output } => {
+            convert(&input, from, to, config.as_deref(), output.as_deref(), strict, warnings)
+        }
+        Command::Export { config, tag, qr } => export(&config, &tag, qr, strict),
+        Command::Qr { link, remote_profile, name, output } => qr(link, remote_profile, name, output),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let mut warnings = Vec::new();
+    let result = run(cli.command, !cli.lenient, &mut warnings);
+    if cli.json {
+        let (mut value, code) = match result {
+            Ok(outcome) => (outcome.json, outcome.code),
+            Err(e) => {
+                let mut value = Map::new();
+                value.insert("error".to_string(), json!(e.to_string()));
+                (value, EXIT_FAILURE)
+            }
+        };
+        value.insert("warnings".to_string(), json!(warnings));
+        println!("{}", Value::Object(value));
+        return ExitCode::from(code);
+    }
+
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    match result {
+        Ok(outcome) => {
+            if !outcome.text.is_empty() {
+                println!("{}", outcome.text);
+            }
+            ExitCode::from(outcome.code)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
 }
@@ -0,0 +1,194 @@
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use super::{RouteRule, SingBoxConfig};
+
+
+/// Keys of a route rule that configure its action rather than match traffic
+const ACTION_OPTIONS: &[&str] = &[
+    "override_address", "override_port", "network_strategy", "network_type", "fallback_network_type",
+    "fallback_delay", "udp_disable_domain_unmapping", "udp_connect", "udp_timeout", "tls_fragment",
+    "tls_fragment_fallback_delay", "tls_record_fragment", "method", "no_drop", "sniffer", "timeout",
+    "strategy", "server",
+];
+
+/// A connection to run through `route.rules`; a rule that looks at an unset field
+/// does not match, like in sing-box when sniffing or the lookup found nothing
+#[derive(Debug, Clone, Default)]
+pub struct Connection {
+    pub inbound: Option<String>,
+    /// Sniffed protocol, e.g. `tls` or `dns`
+    pub protocol: Option<String>,
+    pub domain: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub process: Option<String>,
+}
+
+/// Why the rule at `index` in `route.rules` did not decide where a connection goes
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuleMiss {
+    pub index: usize,
+    pub reasons: Vec<String>,
+}
+
+/// What [`SingBoxConfig::explain`] found: the deciding rule, or `route.final` when
+/// `rule` is `None`, and why each rule before it was passed over
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub rule: Option<usize>,
+    pub action: String,
+    pub outbound: Option<String>,
+    pub misses: Vec<RuleMiss>,
+}
+
+/// Whether `domain` is `suffix` or below it; a suffix starting with a dot only
+/// matches below it
+fn domain_has_suffix(domain: &str, suffix: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    let suffix = suffix.to_lowercase();
+    if suffix.starts_with('.') {
+        return domain.ends_with(&suffix);
+    }
+    domain == suffix || domain.ends_with(&format!(".{}", suffix))
+}
+
+fn cidr_contains(cidr: &str, ip: IpAddr) -> bool {
+    let Some((address, prefix)) = cidr.split_once('/') else {
+        return cidr.parse::<IpAddr>().is_ok_and(|address| address == ip);
+    };
+    let Ok(prefix) = prefix.parse::<u32>() else {
+        return false;
+    };
+    match (address.parse::<IpAddr>(), ip) {
+        (Ok(IpAddr::V4(network)), IpAddr::V4(ip)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (Ok(IpAddr::V6(network)), IpAddr::V6(ip)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// The addresses sing-box's `ip_is_private` matches: private, shared, loopback,
+/// link-local and unspecified ones, and broadcast
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local(),
+    }
+}
+
+impl RouteRule {
+    /// Why this rule does not match `connection`; empty when it matches. Like
+    /// sing-box, the destination conditions `domain_suffix`, `ip_cidr` and
+    /// `ip_is_private` match if any of them does, and every other condition has to.
+    fn mismatches(&self, connection: &Connection) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if let Some(inbound) = &self.inbound {
+            match &connection.inbound {
+                Some(actual) if actual == inbound => {}
+                Some(actual) => reasons.push(format!("inbound {} is not {}", actual, inbound)),
+                None => reasons.push(format!("no inbound to match {}", inbound)),
+            }
+        }
+        if let Some(protocol) = &self.protocol {
+            match &connection.protocol {
+                Some(actual) if actual == protocol => {}
+                Some(actual) => reasons.push(format!("protocol {} is not {}", actual, protocol)),
+                None => reasons.push(format!("no sniffed protocol to match {}", protocol)),
+            }
+        }
+
+        let mut destination = Vec::new();
+        let mut destination_matched = false;
+        if let Some(suffixes) = &self.domain_suffix {
+            match &connection.domain {
+                Some(domain) if suffixes.iter().any(|suffix| domain_has_suffix(domain, suffix)) => destination_matched = true,
+                Some(domain) => destination.push(format!("{} is under no domain_suffix", domain)),
+                None => destination.push("no domain to match domain_suffix".to_string()),
+            }
+        }
+        if let Some(cidrs) = &self.ip_cidr {
+            match connection.ip {
+                Some(ip) if cidrs.iter().any(|cidr| cidr_contains(cidr, ip)) => destination_matched = true,
+                Some(ip) => destination.push(format!("{} is in no ip_cidr", ip)),
+                None => destination.push("no IP address to match ip_cidr".to_string()),
+            }
+        }
+        if let Some(private) = self.ip_is_private {
+            match connection.ip {
+                Some(ip) if is_private(ip) == private => destination_matched = true,
+                Some(ip) if private => destination.push(format!("{} is not private", ip)),
+                Some(ip) => destination.push(format!("{} is private", ip)),
+                None => destination.push("no IP address to match ip_is_private".to_string()),
+            }
+        }
+        if !destination_matched && !destination.is_empty() {
+            reasons.push(destination.join(" and "));
+        }
+
+        if let Some(port) = self.port {
+            match connection.port {
+                Some(actual) if i32::from(actual) == port => {}
+                Some(actual) => reasons.push(format!("port {} is not {}", actual, port)),
+                None => reasons.push(format!("no port to match {}", port)),
+            }
+        }
+        if let Some(names) = &self.process_name {
+            match &connection.process {
+                Some(process) if names.contains(process) => {}
+                Some(process) => reasons.push(format!("process {} is not in process_name", process)),
+                None => reasons.push("no process to match process_name".to_string()),
+            }
+        }
+
+        for key in self.extra.keys().filter(|key| !ACTION_OPTIONS.contains(&key.as_str())) {
+            reasons.push(format!("{} is not simulated", key));
+        }
+        reasons
+    }
+}
+
+impl SingBoxConfig {
+    /// Runs `connection` through `route.rules` in order and reports the first rule
+    /// whose action settles it (`route`, `reject` or `hijack-dns`), or `route.final`
+    /// when none does. Rules with conditions this crate does not model, like
+    /// `rule_set`, are taken not to match. Matching rules with the `sniff`, `resolve`
+    /// or `route-options` actions do not pick an outbound and are listed as misses.
+    pub fn explain(&self, connection: &Connection) -> Explanation {
+        let mut misses = Vec::new();
+        for (index, rule) in self.route.rules.iter().enumerate() {
+            let reasons = rule.mismatches(connection);
+            if !reasons.is_empty() {
+                misses.push(RuleMiss { index, reasons });
+                continue;
+            }
+            let action = rule.action().unwrap_or("route");
+            if matches!(action, "sniff" | "resolve" | "route-options") {
+                misses.push(RuleMiss { index, reasons: vec![format!("matches, but {} does not pick an outbound", action)] });
+                continue;
+            }
+            return Explanation {
+                rule: Some(index),
+                action: action.to_string(),
+                outbound: rule.outbound.clone(),
+                misses,
+            };
+        }
+        Explanation {
+            rule: None,
+            action: "route".to_string(),
+            outbound: self.default_outbound().map(str::to_string),
+            misses,
+        }
+    }
+}
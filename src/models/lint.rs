@@ -0,0 +1,176 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{DnsServer, Outbound, SingBoxConfig};
+
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Likely unintended, but sing-box works
+    Warning,
+    /// Traffic leaks or loops, or connections fail
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A leak or loop [`SingBoxConfig::lint`] found, with what to change about it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// JSON path of the value to change, e.g. `dns.servers[1]`
+    pub path: String,
+    pub message: String,
+    pub fix: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {} (fix: {})", self.severity, self.path, self.message, self.fix)
+    }
+}
+
+/// Whether sing-box answers queries to `address` itself or from the OS, without
+/// connecting anywhere through an outbound
+fn is_local_dns(address: &str) -> bool {
+    address == "local" || address == "fakeip" || address.starts_with("dhcp://") || address.starts_with("rcode://")
+}
+
+impl DnsServer {
+    fn detour(&self) -> Option<&str> {
+        self.extra.get("detour").and_then(Value::as_str)
+    }
+}
+
+impl SingBoxConfig {
+    /// Whether `tag` is a proxy or a group that can pick one
+    fn through_proxy(&self, tag: &str, seen: &mut Vec<String>) -> bool {
+        if seen.iter().any(|seen| seen == tag) {
+            return false;
+        }
+        seen.push(tag.to_string());
+        match self.outbounds.iter().find(|out| out.tag() == tag) {
+            Some(out) if out.is_proxy() => true,
+            Some(out) => out.members().iter().any(|member| self.through_proxy(member, seen)),
+            None => false,
+        }
+    }
+
+    /// The outbound a DNS server's queries go through when that is a proxy
+    fn dns_proxy<'a>(&'a self, server: &'a DnsServer) -> Option<&'a str> {
+        if is_local_dns(&server.address) {
+            return None;
+        }
+        let via = server.detour().or(self.default_outbound())?;
+        self.through_proxy(via, &mut Vec::new()).then_some(via)
+    }
+
+    /// Looks for the leaks and routing loops templates commonly have: a tun inbound
+    /// with `auto_route` whose DNS queries nothing hijacks, `strict_route` without
+    /// `auto_route`, `auto_route` without `route.auto_detect_interface`, DNS servers
+    /// that go through a proxy for lack of a `detour`, and proxy hostnames that only a
+    /// DNS server behind a proxy can resolve.
+    pub fn lint(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let direct = self.outbounds.iter()
+            .find(|out| matches!(out, Outbound::Direct(_)))
+            .map(|out| out.tag().to_string());
+        let direct_hint = direct.as_deref().unwrap_or("a direct outbound");
+
+        let dns_outbound = |tag: Option<&String>| tag.is_some_and(|tag| {
+            self.outbounds.iter().any(|out| out.tag() == tag && matches!(out, Outbound::Dns(_)))
+        });
+        let hijacks_dns = self.route.rules.iter().any(|rule| {
+            rule.action.as_deref() == Some("hijack-dns")
+                || ((rule.protocol.as_deref() == Some("dns") || rule.port == Some(53)) && dns_outbound(rule.outbound.as_ref()))
+        });
+
+        let mut auto_route = false;
+        for (i, inbound) in self.inbounds.iter().enumerate() {
+            if inbound.type_field != "tun" {
+                continue;
+            }
+            let path = format!("inbounds[{}]", i);
+            if inbound.auto_route == Some(true) {
+                auto_route = true;
+                if !hijacks_dns {
+                    findings.push(Finding {
+                        severity: Severity::Error,
+                        path: "route.rules".to_string(),
+                        message: format!("{:?} captures DNS queries with auto_route but no rule hijacks them, so they leak to the system resolver", inbound.tag),
+                        fix: "add {\"protocol\": \"dns\", \"action\": \"hijack-dns\"} as the first route rule".to_string(),
+                    });
+                }
+            } else if inbound.strict_route == Some(true) {
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    path: format!("{}.strict_route", path),
+                    message: format!("{:?} sets strict_route, which does nothing without auto_route", inbound.tag),
+                    fix: "set auto_route to true, or drop strict_route".to_string(),
+                });
+            }
+        }
+        let detects_interface = self.route.auto_detect_interface;
+        if auto_route && detects_interface != Some(true) && !self.route.extra.contains_key("default_interface") {
+            let state = if detects_interface.is_none() { "missing" } else { "false" };
+            findings.push(Finding {
+                severity: Severity::Error,
+                path: "route.auto_detect_interface".to_string(),
+                message: format!("is {} and there is no default_interface, so with auto_route connections to the proxy servers are routed back into the tun", state),
+                fix: "set route.auto_detect_interface to true".to_string(),
+            });
+        }
+
+        for (i, server) in self.dns.servers.iter().enumerate() {
+            if server.detour().is_some() {
+                continue;
+            }
+            if let Some(via) = self.dns_proxy(server) {
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    path: format!("dns.servers[{}]", i),
+                    message: format!("{:?} has no detour, so its queries go through the proxy {:?}", server.tag, via),
+                    fix: format!("set \"detour\" to {:?} to query it directly, or to the proxy to make that choice explicit", direct_hint),
+                });
+            }
+        }
+
+        // An outbound's own domain_resolver wins over the route default, which wins over dns.final
+        for (i, out) in self.outbounds.iter().enumerate() {
+            let Some((server, _)) = out.server() else {
+                continue;
+            };
+            if server.parse::<IpAddr>().is_ok() {
+                continue;
+            }
+            let resolver = out.extra().get("domain_resolver")
+                .and_then(|resolver| resolver.as_str().or(resolver.get("server").and_then(Value::as_str)))
+                .or(self.route.default_domain_resolver.as_deref())
+                .unwrap_or(&self.dns.final_field);
+            let Some(dns) = self.dns.servers.iter().find(|dns| dns.tag == resolver) else {
+                continue;
+            };
+            if let Some(via) = self.dns_proxy(dns) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    path: format!("outbounds[{}].server", i),
+                    message: format!("{} is resolved by the DNS server {:?}, which is itself reached through {:?}", server, dns.tag, via),
+                    fix: format!("set \"detour\": {:?} on DNS server {:?}, or resolve proxy servers with a local DNS server", direct_hint, dns.tag),
+                });
+            }
+        }
+
+        findings
+    }
+}
@@ -1,29 +1,48 @@
 use serde::{Serialize, Deserialize};
 use serde::Deserializer;
-use std::collections::HashMap;
 use derivative::Derivative;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use crate::url_parser::parse_url;
-use std::error::Error;
+use std::path::Path;
+use crate::clash::{proxy_name, proxy_to_link, ClashGroup, ClashImport, ClashProfile, SkippedGroup};
+use crate::error::Error;
+use crate::subscription::{DroppedParam, ImportEntry, ImportReport, SkippedLink};
+use crate::url_parser::{decode_base64, format_vmess_link, parse_share_link, Host, ShareLink};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use serde_json::{Map,Value};
 
+#[allow(clippy::module_inception)]
 pub mod models;
+mod explain;
+mod lint;
+mod transport;
+mod validate;
+pub use explain::*;
+pub use lint::*;
+pub use transport::*;
+pub use validate::*;
 
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
 #[derivative(Default)]
 pub struct DnsServer {
     #[derivative(Default(value="String::from(\"default\")"))]
-    address: String,
-    tag: String,
+    pub(crate) address: String,
+    pub(crate) tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone, Default)]
 pub struct Dns {
     #[serde(rename = "final")]
     #[derivative(Default(value="String::from(\"default\")"))]
-    final_field: String,
-    servers: Vec<DnsServer>,
+    pub(crate) final_field: String,
+    pub(crate) servers: Vec<DnsServer>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,16 +61,20 @@ pub struct Inbound {
     strict_route: Option<bool>,
     tag: String,
     #[serde(rename = "type")]
-    type_field: String,
+    pub(crate) type_field: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     stack: Option<String>,
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Log {
-    level: String,
+    pub(crate) level: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     timestamp: Option<bool>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
@@ -61,6 +84,8 @@ pub struct DirectOutbound {
     #[serde(rename = "type")]
     #[derivative(Default(value="String::from(\"direct\")"))]
     type_field: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
@@ -72,6 +97,8 @@ pub struct SocksOutbound {
     type_field: String,
     server: String,
     server_port: i32,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
@@ -81,112 +108,725 @@ pub struct DnsOutbound {
     #[serde(rename = "type")]
     #[derivative(Default(value="String::from(\"dns\")"))]
     type_field: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RealityConfig {
     enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    public_key: Option<String>,
-    short_id: String,
+    pub(crate) public_key: Option<String>,
+    pub(crate) short_id: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UtlsConfig {
     enabled: bool,
-    fingerprint: String,
+    pub(crate) fingerprint: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TlsConfig {
-    enabled: bool,
+    pub(crate) enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alpn: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) insecure: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    reality: Option<RealityConfig>,
-    server_name: String,
+    pub(crate) reality: Option<RealityConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    utls: Option<UtlsConfig>,
+    pub(crate) server_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) utls: Option<UtlsConfig>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
 #[derivative(Default)]
 pub struct VlessOutbound {
-    packet_encoding: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    packet_encoding: Option<String>,
+    pub(crate) server: String,
+    pub(crate) server_port: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tls: Option<TlsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) transport: Option<V2RayTransport>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"vless\")"))]
+    type_field: String,
+    tag: String,
+    pub(crate) uuid: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct VmessOutbound {
+    pub(crate) server: String,
+    pub(crate) server_port: i32,
+    pub(crate) uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) security: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alter_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    packet_encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tls: Option<TlsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) transport: Option<V2RayTransport>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"vmess\")"))]
+    type_field: String,
+    tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct TrojanOutbound {
+    pub(crate) server: String,
+    pub(crate) server_port: i32,
+    pub(crate) password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tls: Option<TlsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) transport: Option<V2RayTransport>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"trojan\")"))]
+    type_field: String,
+    tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct ShadowsocksOutbound {
+    pub(crate) server: String,
+    pub(crate) server_port: i32,
+    pub(crate) method: String,
+    pub(crate) password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) plugin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plugin_opts: Option<String>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"shadowsocks\")"))]
+    type_field: String,
+    tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hysteria2Obfs {
+    #[serde(rename = "type")]
+    type_field: String,
+    password: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct Hysteria2Outbound {
     server: String,
     server_port: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
+    server_ports: Option<Vec<String>>,
+    password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    obfs: Option<Hysteria2Obfs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     tls: Option<TlsConfig>,
     #[serde(rename = "type")]
-    #[derivative(Default(value="String::from(\"vless\")"))]
+    #[derivative(Default(value="String::from(\"hysteria2\")"))]
+    type_field: String,
+    tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct TuicOutbound {
+    server: String,
+    server_port: i32,
+    uuid: String,
+    password: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    congestion_control: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    udp_relay_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls: Option<TlsConfig>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"tuic\")"))]
     type_field: String,
     tag: String,
-    uuid: String
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct SelectorOutbound {
+    pub(crate) outbounds: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) default: Option<String>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"selector\")"))]
+    type_field: String,
+    pub(crate) tag: String,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Derivative, Debug, Clone)]
+#[derivative(Default)]
+pub struct UrlTestOutbound {
+    pub(crate) outbounds: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) interval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tolerance: Option<u32>,
+    #[serde(rename = "type")]
+    #[derivative(Default(value="String::from(\"urltest\")"))]
+    pub(crate) type_field: String,
+    pub(crate) tag: String,
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Debug, Clone)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Outbound {
     Direct(DirectOutbound),
     Dns(DnsOutbound),
     Vless(VlessOutbound),
+    Vmess(VmessOutbound),
+    Trojan(TrojanOutbound),
+    Shadowsocks(ShadowsocksOutbound),
+    Hysteria2(Hysteria2Outbound),
+    Tuic(TuicOutbound),
+    Selector(SelectorOutbound),
+    UrlTest(UrlTestOutbound),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupStrategy {
+    #[default]
+    None,
+    /// A manual `selector` over the servers
+    Selector,
+    /// An automatic `urltest` over the servers
+    UrlTest,
+    /// A `urltest` plus a `selector` offering it and every server
+    Both,
+}
+
+/// How to group the proxy outbounds; unset test options keep sing-box's defaults
+#[derive(Debug, Clone, Default)]
+pub struct GroupOptions {
+    pub strategy: GroupStrategy,
+    pub url: Option<String>,
+    pub interval: Option<String>,
+    pub tolerance: Option<u32>,
+}
+
+/// A part of a config that a conversion to or from another format had to leave out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmapped {
+    /// JSON path of the part in the source config, e.g. `route.rules[2].process_name`
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Unmapped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Tags of the generated groups
+pub const SELECTOR_TAG: &str = "select";
+pub const URLTEST_TAG: &str = "auto";
+const GROUP_TAGS: [&str; 2] = [SELECTOR_TAG, URLTEST_TAG];
+
+/// sing-box outbound type a share link scheme maps to
+pub fn outbound_type(scheme: &str) -> Option<&'static str> {
+    match scheme {
+        "vless" => Some("vless"),
+        "vmess" => Some("vmess"),
+        "trojan" => Some("trojan"),
+        "ss" => Some("shadowsocks"),
+        "hysteria2" | "hy2" => Some("hysteria2"),
+        "tuic" => Some("tuic"),
+        _ => None,
+    }
+}
+
+/// `tag`, or `tag-2`, `tag-3`, ... if it is already taken
+pub(crate) fn unique_tag(tag: &str, taken: &[String]) -> String {
+    let mut candidate = tag.to_string();
+    let mut n = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{}-{}", tag, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// The outbounds of `entries` whose server and port neither `existing` nor an
+/// earlier entry has, tagged apart from `existing`, each other and the groups
+fn dedupe_entries(entries: Vec<ImportEntry>, existing: &[&Outbound]) -> (Vec<Outbound>, ImportReport) {
+    let mut report = ImportReport::default();
+    let mut imported: Vec<Outbound> = Vec::new();
+    let mut tags: Vec<String> = GROUP_TAGS.iter()
+        .map(|tag| tag.to_string())
+        .chain(existing.iter().map(|out| out.tag().to_string()))
+        .collect();
+
+    for (i, (source, outbound)) in entries.into_iter().enumerate() {
+        let outbound = outbound.and_then(|mut outbound| {
+            if let Some((server, port)) = outbound.server()
+                && let Some(taken) = existing.iter().copied().chain(&imported).find(|out| out.server() == Some((server, port))) {
+                return Err(Error::DuplicateServer {
+                    server: server.to_string(),
+                    port,
+                    tag: taken.tag().to_string(),
+                });
+            }
+            outbound.set_tag(unique_tag(outbound.tag(), &tags));
+            Ok(outbound)
+        });
+
+        match outbound {
+            Ok(outbound) => {
+                tags.push(outbound.tag().to_string());
+                report.imported.push(outbound.tag().to_string());
+                imported.push(outbound);
+            }
+            Err(reason) => report.skipped.push(SkippedLink { line: i + 1, link: source, reason }),
+        }
+    }
+
+    (imported, report)
+}
+
+/// Tag for an outbound created from a link rather than from a template
+fn link_tag(link: &ShareLink) -> String {
+    link.fragment.as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| outbound_type(&link.scheme).unwrap_or("proxy").to_string())
 }
+
 impl Outbound {
+    pub fn tag(&self) -> &str {
+        match self {
+            Outbound::Direct(out) => &out.tag,
+            Outbound::Dns(out) => &out.tag,
+            Outbound::Vless(out) => &out.tag,
+            Outbound::Vmess(out) => &out.tag,
+            Outbound::Trojan(out) => &out.tag,
+            Outbound::Shadowsocks(out) => &out.tag,
+            Outbound::Hysteria2(out) => &out.tag,
+            Outbound::Tuic(out) => &out.tag,
+            Outbound::Selector(out) => &out.tag,
+            Outbound::UrlTest(out) => &out.tag,
+        }
+    }
+
+    fn set_tag(&mut self, tag: String) {
+        match self {
+            Outbound::Direct(out) => out.tag = tag,
+            Outbound::Dns(out) => out.tag = tag,
+            Outbound::Vless(out) => out.tag = tag,
+            Outbound::Vmess(out) => out.tag = tag,
+            Outbound::Trojan(out) => out.tag = tag,
+            Outbound::Shadowsocks(out) => out.tag = tag,
+            Outbound::Hysteria2(out) => out.tag = tag,
+            Outbound::Tuic(out) => out.tag = tag,
+            Outbound::Selector(out) => out.tag = tag,
+            Outbound::UrlTest(out) => out.tag = tag,
+        }
+    }
+
+    /// Keys of the outbound the models don't cover, e.g. `detour` or `domain_resolver`
+    fn extra(&self) -> &Map<String, Value> {
+        match self {
+            Outbound::Direct(out) => &out.extra,
+            Outbound::Dns(out) => &out.extra,
+            Outbound::Vless(out) => &out.extra,
+            Outbound::Vmess(out) => &out.extra,
+            Outbound::Trojan(out) => &out.extra,
+            Outbound::Shadowsocks(out) => &out.extra,
+            Outbound::Hysteria2(out) => &out.extra,
+            Outbound::Tuic(out) => &out.extra,
+            Outbound::Selector(out) => &out.extra,
+            Outbound::UrlTest(out) => &out.extra,
+        }
+    }
+
+    fn extra_mut(&mut self) -> &mut Map<String, Value> {
+        match self {
+            Outbound::Direct(out) => &mut out.extra,
+            Outbound::Dns(out) => &mut out.extra,
+            Outbound::Vless(out) => &mut out.extra,
+            Outbound::Vmess(out) => &mut out.extra,
+            Outbound::Trojan(out) => &mut out.extra,
+            Outbound::Shadowsocks(out) => &mut out.extra,
+            Outbound::Hysteria2(out) => &mut out.extra,
+            Outbound::Tuic(out) => &mut out.extra,
+            Outbound::Selector(out) => &mut out.extra,
+            Outbound::UrlTest(out) => &mut out.extra,
+        }
+    }
+
+    /// Whether this outbound is a proxy server a share link can describe
+    pub fn is_proxy(&self) -> bool {
+        !matches!(self, Outbound::Direct(_) | Outbound::Dns(_) | Outbound::Selector(_) | Outbound::UrlTest(_))
+    }
+
+    /// `server` and `server_port` of a proxy outbound
+    pub fn server(&self) -> Option<(&str, i32)> {
+        match self {
+            Outbound::Vless(out) => Some((&out.server, out.server_port)),
+            Outbound::Vmess(out) => Some((&out.server, out.server_port)),
+            Outbound::Trojan(out) => Some((&out.server, out.server_port)),
+            Outbound::Shadowsocks(out) => Some((&out.server, out.server_port)),
+            Outbound::Hysteria2(out) => Some((&out.server, out.server_port)),
+            Outbound::Tuic(out) => Some((&out.server, out.server_port)),
+            Outbound::Direct(_) | Outbound::Dns(_) | Outbound::Selector(_) | Outbound::UrlTest(_) => None,
+        }
+    }
+
+    /// Tags a selector or urltest chooses from; empty for other outbounds
+    pub fn members(&self) -> &[String] {
+        match self {
+            Outbound::Selector(out) => &out.outbounds,
+            Outbound::UrlTest(out) => &out.outbounds,
+            _ => &[],
+        }
+    }
+
+    /// Value of the `type` key
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Outbound::Direct(_) => "direct",
+            Outbound::Dns(_) => "dns",
+            Outbound::Vless(_) => "vless",
+            Outbound::Vmess(_) => "vmess",
+            Outbound::Trojan(_) => "trojan",
+            Outbound::Shadowsocks(_) => "shadowsocks",
+            Outbound::Hysteria2(_) => "hysteria2",
+            Outbound::Tuic(_) => "tuic",
+            Outbound::Selector(_) => "selector",
+            Outbound::UrlTest(_) => "urltest",
+        }
+    }
+
+    /// Builds the outbound a share link describes. Fields the link doesn't set are
+    /// taken from `template` when it is an outbound of the same protocol.
+    pub fn from_link(link: &ShareLink, template: Option<&Outbound>) -> Result<Outbound, Error> {
+        match link.scheme.as_str() {
+            "vless" => {
+                let mut vless = match template {
+                    Some(Outbound::Vless(vless)) => vless.clone(),
+                    _ => VlessOutbound { tag: link_tag(link), ..Default::default() },
+                };
+                vless.enrich(link)?;
+                Ok(Outbound::Vless(vless))
+            }
+            "vmess" => {
+                let mut vmess = match template {
+                    Some(Outbound::Vmess(vmess)) => vmess.clone(),
+                    _ => VmessOutbound { tag: link_tag(link), ..Default::default() },
+                };
+                vmess.enrich(link)?;
+                Ok(Outbound::Vmess(vmess))
+            }
+            "trojan" => {
+                let mut trojan = match template {
+                    Some(Outbound::Trojan(trojan)) => trojan.clone(),
+                    _ => TrojanOutbound { tag: link_tag(link), ..Default::default() },
+                };
+                trojan.enrich(link)?;
+                Ok(Outbound::Trojan(trojan))
+            }
+            "ss" => {
+                let mut shadowsocks = match template {
+                    Some(Outbound::Shadowsocks(shadowsocks)) => shadowsocks.clone(),
+                    _ => ShadowsocksOutbound { tag: link_tag(link), ..Default::default() },
+                };
+                shadowsocks.enrich(link)?;
+                Ok(Outbound::Shadowsocks(shadowsocks))
+            }
+            "hysteria2" | "hy2" => {
+                let mut hysteria2 = match template {
+                    Some(Outbound::Hysteria2(hysteria2)) => hysteria2.clone(),
+                    _ => Hysteria2Outbound { tag: link_tag(link), ..Default::default() },
+                };
+                hysteria2.enrich(link)?;
+                Ok(Outbound::Hysteria2(hysteria2))
+            }
+            "tuic" => {
+                let mut tuic = match template {
+                    Some(Outbound::Tuic(tuic)) => tuic.clone(),
+                    _ => TuicOutbound { tag: link_tag(link), ..Default::default() },
+                };
+                tuic.enrich(link)?;
+                Ok(Outbound::Tuic(tuic))
+            }
+            other => Err(Error::UnsupportedProtocol(other.to_string())),
+        }
+    }
+
+    /// Parameters of `link` that [`from_link`](Self::from_link) accepts but that no
+    /// sing-box option carries, each with what happens instead
+    pub fn dropped_params(link: &ShareLink) -> Vec<(&'static str, &'static str)> {
+        let mut dropped = Vec::new();
+        if outbound_type(&link.scheme) == Some("hysteria2") && link.param("pinSHA256").is_some_and(|pin| !pin.is_empty()) {
+            // certificate_public_key_sha256 hashes the public key, not the certificate
+            dropped.push(("pinSHA256", "sing-box cannot pin certificate hashes, the certificate is verified normally"));
+        }
+        dropped
+    }
+
+    /// The share link describing this outbound, with the tag as its `#name`. It reads
+    /// back into the same outbound through [`from_link`](Self::from_link).
+    pub fn to_link(&self) -> Result<ShareLink, Error> {
+        match self {
+            Outbound::Vless(out) => out.to_link(),
+            Outbound::Vmess(out) => out.to_link(),
+            Outbound::Trojan(out) => out.to_link(),
+            Outbound::Shadowsocks(out) => out.to_link(),
+            Outbound::Hysteria2(out) => out.to_link(),
+            Outbound::Tuic(out) => out.to_link(),
+            Outbound::Direct(_) | Outbound::Dns(_) | Outbound::Selector(_) | Outbound::UrlTest(_) => {
+                Err(Error::UnsupportedProtocol(self.type_name().to_string()))
+            }
+        }
+    }
+
+    /// [`to_link`](Self::to_link) as text. VMess is written in the v2rayN
+    /// `vmess://base64(json)` form other clients expect.
+    pub fn to_share_link(&self) -> Result<String, Error> {
+        match self {
+            Outbound::Vmess(out) => Ok(format_vmess_link(&out.to_link()?)),
+            _ => Ok(self.to_link()?.to_string()),
+        }
+    }
+
     fn deserialize_outbound<'de, D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        // Deserialize into a HashMap to access fields dynamically
-        let map: HashMap<String, serde_json::Value> = Deserialize::deserialize(deserializer)?;
+        // Deserialize into a Map to access fields dynamically; whatever is left
+        // after the known fields are taken out is kept as the outbound's extra keys
+        let map: Map<String, Value> = Deserialize::deserialize(deserializer)?;
+        Self::from_map(map, false).map_err(serde::de::Error::custom)
+    }
 
+    /// Builds an outbound from its JSON the way sing-box reads it. Unlike the
+    /// [`Deserialize`] impl, which fills in placeholders for a missing `uuid`, `server`
+    /// or `server_port` of a VLESS outbound and drops a `tls` block it cannot read, a
+    /// missing or ill-typed field is an error naming its path.
+    pub fn from_value_strict(value: Value) -> Result<Outbound, Error> {
+        let Value::Object(map) = value else {
+            return Err(Error::InvalidField { field: String::new(), reason: "an outbound must be an object".to_string() });
+        };
+        Ok(Self::from_map(map, true)?)
+    }
 
-        let type_str: &str = map.get("type").unwrap_or_default().as_str().unwrap_or("direct");
-        let tag = map.get("tag").ok_or_else(|| serde::de::Error::missing_field("tag"))?;
+    fn from_map(mut map: Map<String, Value>, strict: bool) -> Result<Self, FieldError> {
+        let type_str = match map.remove("type") {
+            Some(Value::String(type_str)) => type_str,
+            Some(other) if strict => return Err(FieldError::new("type", format!("expected a string, found {}", other))),
+            None if strict => return Err(FieldError::new("type", "missing field")),
+            _ => "direct".to_string(),
+        };
+        let tag = match map.remove("tag") {
+            Some(Value::String(tag)) => tag,
+            Some(other) if strict => return Err(FieldError::new("tag", format!("expected a string, found {}", other))),
+            Some(_) => String::new(),
+            None => return Err(FieldError::new("tag", "missing field")),
+        };
 
-        match type_str {
+        match type_str.as_str() {
             "direct" => {
                 let direct_outbound = DirectOutbound {
-                    tag: tag.as_str().unwrap().to_string(),
-                    type_field: type_str.to_string()
+                    tag,
+                    type_field: type_str,
+                    extra: map,
                 };
                 Ok(Outbound::Direct(direct_outbound))
             }
             "dns" => {
-
-                let dns_outbound: DnsOutbound = serde_json::from_value(serde_json::json!(map)).map_err(serde::de::Error::custom)?;
+                let dns_outbound = DnsOutbound {
+                    tag,
+                    type_field: type_str,
+                    extra: map,
+                };
                 Ok(Outbound::Dns(dns_outbound))
             }
             "vless" => {
-                let uuid = map.get("uuid")
-                    .unwrap_or(&serde_json::Value::String("default_uuid".to_string()))
-                    .as_str().unwrap_or("default_encoding").to_string();
-                let packet_encoding = map.get("packet_encoding")
-                    .unwrap_or(&serde_json::Value::String("default_encoding".to_string()))
-                    .as_str().unwrap_or("default_encoding").to_string();
-
-                let server = map.get("server")
-                    .unwrap_or(&serde_json::Value::String("default_server".to_string()))
-                    .as_str().unwrap_or("default_server").to_string();
-
-                let server_port = map.get("server_port")
-                    .and_then(|v| v.as_i64())
-                    .map(|p| p as i32)
-                    .unwrap_or(8080); // Default port
-
-                let tls = map.get("tls").and_then(|tls_value| {
-                    serde_json::from_value(tls_value.clone()).ok()
-                });
+                let uuid = take_string(&mut map, "uuid", strict)?
+                    .unwrap_or_else(|| "default_uuid".to_string());
+                let flow = take_optional_string(&mut map, "flow", strict)?;
+                let packet_encoding = take_optional_string(&mut map, "packet_encoding", strict)?;
+                let server = take_string(&mut map, "server", strict)?
+                    .unwrap_or_else(|| "default_server".to_string());
+
+                let server_port = match map.remove("server_port") {
+                    Some(Value::Number(port)) if port.as_i64().is_some_and(|port| i32::try_from(port).is_ok()) => port.as_i64().unwrap_or_default() as i32,
+                    Some(other) if strict => return Err(FieldError::new("server_port", format!("expected a port number, found {}", other))),
+                    None if strict => return Err(FieldError::new("server_port", "missing field")),
+                    _ => 8080, // Default port
+                };
+
+                let tls = match map.remove("tls") {
+                    Some(tls_value) if strict => Some(from_value_at(tls_value).map_err(|e| e.within("tls"))?),
+                    Some(tls_value) => serde_json::from_value(tls_value).ok(),
+                    None => None,
+                };
+                // A transport this crate doesn't model is kept verbatim among the extra keys
+                let transport = match map.remove("transport") {
+                    Some(value) => match serde_json::from_value(value.clone()) {
+                        Ok(transport) => Some(transport),
+                        Err(_) => {
+                            map.insert("transport".to_string(), value);
+                            None
+                        }
+                    },
+                    None => None,
+                };
                 let vless_outbound = VlessOutbound {
-                    tag: tag.to_string(),
+                    tag,
+                    flow,
                     packet_encoding,
                     server,
                     server_port,
-                    type_field: "vless".to_string(), 
-                    tls: tls,
-                    uuid: uuid
+                    type_field: "vless".to_string(),
+                    tls,
+                    transport,
+                    uuid,
+                    extra: map,
                 };
                 Ok(Outbound::Vless(vless_outbound))
             }
-            _ => Err(serde::de::Error::custom(format!("unknown type: {}", type_str))),
+            "vmess" | "trojan" | "shadowsocks" | "hysteria2" | "tuic" | "selector" | "urltest" => {
+                map.insert("tag".to_string(), Value::String(tag));
+                map.insert("type".to_string(), Value::String(type_str.clone()));
+                let map = Value::Object(map);
+                Ok(match type_str.as_str() {
+                    "vmess" => Outbound::Vmess(from_value_at(map)?),
+                    "trojan" => Outbound::Trojan(from_value_at(map)?),
+                    "shadowsocks" => Outbound::Shadowsocks(from_value_at(map)?),
+                    "hysteria2" => Outbound::Hysteria2(from_value_at(map)?),
+                    "tuic" => Outbound::Tuic(from_value_at(map)?),
+                    "selector" => Outbound::Selector(from_value_at(map)?),
+                    _ => Outbound::UrlTest(from_value_at(map)?),
+                })
+            }
+            _ => Err(FieldError::new("type", format!("unknown type: {}", type_str))),
+        }
+    }
+}
+
+/// Where in a config reading it failed
+#[derive(Debug)]
+struct FieldError {
+    /// JSON path relative to the value being read; empty for the value itself
+    path: String,
+    reason: String,
+}
+
+impl FieldError {
+    fn new(path: &str, reason: impl Into<String>) -> FieldError {
+        FieldError { path: path.to_string(), reason: reason.into() }
+    }
+
+    /// The same error seen from the object that holds the value under `key`
+    fn within(self, key: &str) -> FieldError {
+        let path = match self.path.as_str() {
+            "" => key.to_string(),
+            path if path.starts_with('[') => format!("{}{}", key, path),
+            path => format!("{}.{}", key, path),
+        };
+        FieldError { path, reason: self.reason }
+    }
+}
+
+impl From<FieldError> for Error {
+    fn from(e: FieldError) -> Self {
+        Error::InvalidField { field: e.path, reason: e.reason }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.reason)
+        } else {
+            write!(f, "{}: {}", self.path, self.reason)
+        }
+    }
+}
+
+/// `serde_json::from_value` that tells which field it failed on
+fn from_value_at<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, FieldError> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = match e.path().to_string().as_str() {
+            "." => String::new(),
+            path => path.to_string(),
+        };
+        let reason = e.into_inner().to_string();
+        // serde names a missing field in the message rather than in the path
+        let missing = reason.strip_prefix("missing field `")
+            .and_then(|rest| rest.strip_suffix('`'))
+            .map(str::to_string);
+        match missing {
+            Some(field) if path.is_empty() => FieldError::new(&field, "missing field"),
+            Some(field) => FieldError::new(&format!("{}.{}", path, field), "missing field"),
+            None => FieldError::new(&path, reason),
         }
+    })
+}
+
+/// A string field; a strict read requires it, a lenient one lets the caller fill in
+/// a placeholder
+fn take_string(map: &mut Map<String, Value>, key: &str, strict: bool) -> Result<Option<String>, FieldError> {
+    match map.remove(key) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(other) if strict => Err(FieldError::new(key, format!("expected a string, found {}", other))),
+        None if strict => Err(FieldError::new(key, "missing field")),
+        _ => Ok(None),
+    }
+}
+
+fn take_optional_string(map: &mut Map<String, Value>, key: &str, strict: bool) -> Result<Option<String>, FieldError> {
+    match map.remove(key) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(other) if strict => Err(FieldError::new(key, format!("expected a string, found {}", other))),
+        _ => Ok(None),
     }
 }
 
@@ -199,161 +839,933 @@ impl<'de> Deserialize<'de> for Outbound {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct RouteRule {
     #[serde(skip_serializing_if = "Option::is_none")]
-    inbound: Option<String>,
+    pub(crate) inbound: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    outbound: Option<String>,
+    pub(crate) outbound: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    action: Option<String>,
+    pub(crate) action: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    protocol: Option<String>,
+    pub(crate) protocol: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    ip_cidr: Option<Vec<String>>,
+    pub(crate) ip_cidr: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    domain_suffix: Option<Vec<String>>,
+    pub(crate) domain_suffix: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    ip_is_private: Option<bool>,
+    pub(crate) ip_is_private: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    port: Option<i32>,
+    pub(crate) port: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    process_name: Option<Vec<String>>,
+    pub(crate) process_name: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub(crate) extra: Map<String, Value>,
 }
 
 
+impl RouteRule {
+    pub fn outbound(&self) -> Option<&str> {
+        self.outbound.as_deref()
+    }
+
+    /// `action`, which sing-box takes to be `route` when it is missing
+    pub fn action(&self) -> Option<&str> {
+        self.action.as_deref()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Route {
-    auto_detect_interface: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_detect_interface: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     default_domain_resolver: Option<String>,
     #[serde(rename = "final")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    final_field: Option<String>,
-    rules: Vec<RouteRule>,
+    pub(crate) final_field: Option<String>,
+    pub(crate) rules: Vec<RouteRule>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SingBoxConfig {
-    dns: Dns,
-    inbounds: Vec<Inbound>,
-    log: Log,
+    pub(crate) dns: Dns,
+    pub(crate) inbounds: Vec<Inbound>,
+    pub(crate) log: Log,
     outbounds: Vec<Outbound>,
-    route: Route
+    pub(crate) route: Route,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
-impl VlessOutbound {
-    fn enrich(&mut self, root_params: Map<String, Value>) {
-        self.server = root_params.get("host").unwrap_or_default().to_string();
-        self.server_port = root_params.get("port").unwrap_or_default().as_i64().expect("Incorrect port specified") as i32;
-        self.uuid = root_params.get("uuid").unwrap_or_default().to_string();
-        let params = root_params.get("params").unwrap_or_default();
+/// `server` and `server_port` of a link whose port is required
+fn link_server(link: &ShareLink) -> Result<(String, i32), Error> {
+    let port = link.port
+        .filter(|port| *port != 0)
+        .ok_or_else(|| Error::invalid_link("port", "a non-zero port is required"))?;
+    Ok((link.host.to_string(), i32::from(port)))
+}
+
+/// The user-info a link carries its credentials in, reported as `field` when missing
+fn link_credentials(link: &ShareLink, field: &str, reason: &str) -> Result<String, Error> {
+    link.user_info.clone()
+        .filter(|user_info| !user_info.is_empty())
+        .ok_or_else(|| Error::invalid_link(field, reason))
+}
+
+impl TlsConfig {
+    /// [`from_link`](Self::from_link) on top of an outbound's current tls block,
+    /// keeping whatever the template carried beyond the fields a link sets
+    fn merge_link(link: &ShareLink, default_security: &str, template: Option<TlsConfig>) -> Result<TlsConfig, Error> {
+        let extra = template.map(|tls| tls.extra).unwrap_or_default();
+        TlsConfig::from_link(link, default_security, extra)
+    }
 
+    /// Builds the tls block described by a share link's `security`, `sni`, `alpn`,
+    /// `fp`, `allowInsecure`, `pbk` and `sid` parameters. A link without `security`
+    /// gets `default_security`: `none` for VLESS and VMess like Xray and v2rayN do,
+    /// `tls` for Trojan.
+    fn from_link(link: &ShareLink, default_security: &str, extra: Map<String, Value>) -> Result<TlsConfig, Error> {
         let mut tls = TlsConfig {
             enabled: true,
+            alpn: None,
+            insecure: None,
             reality: None,
-            server_name: String::new(),
+            server_name: link.param("sni").or(link.param("peer")).filter(|sni| !sni.is_empty()).map(str::to_string),
             utls: None,
+            extra,
         };
-        
-        if let Some(security) = params.get("security") {
-            match security {
-                Value::String(s) if s == "reality" => {
-                    let reality = RealityConfig {
-                        enabled: true,
-                        public_key: Some(params
-                            .get("pbk")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_default()),
-                        short_id: params
-                            .get("sid")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_default(),
-                    };
-                    tls.reality = Some(reality);
+
+        match link.param("security").filter(|security| !security.is_empty()).unwrap_or(default_security) {
+            "none" => {
+                tls.enabled = false;
+                tls.server_name = None;
+                return Ok(tls);
+            }
+            "tls" => {
+                let alpn: Vec<String> = link.params("alpn").iter()
+                    .flat_map(|alpn| alpn.split(','))
+                    .map(str::trim)
+                    .filter(|alpn| !alpn.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if !alpn.is_empty() {
+                    tls.alpn = Some(alpn);
+                }
+                let insecure = link.param("allowInsecure")
+                    .or(link.param("allow_insecure"))
+                    .or(link.param("insecure"));
+                if let Some(insecure) = insecure {
+                    tls.insecure = Some(matches!(insecure, "1" | "true"));
                 }
-                _ => todo!(),
             }
+            "reality" => {
+                let reality = RealityConfig {
+                    enabled: true,
+                    public_key: Some(link.param("pbk").unwrap_or_default().to_string()),
+                    short_id: link.param("sid").unwrap_or_default().to_string(),
+                    extra: Map::new(),
+                };
+                tls.reality = Some(reality);
+            }
+            other => return Err(Error::UnsupportedSecurity(other.to_string())),
         }
 
-        if let Some(sni) = params.get("sni") {
-            if let Some(s) = sni.as_str() {
-                tls.server_name = s.to_string();
+        if let Some(fp) = link.param("fp").filter(|fp| !fp.is_empty()) {
+            tls.utls = Some(UtlsConfig {
+                enabled: true,
+                fingerprint: fp.to_string(),
+                extra: Map::new(),
+            });
+        }
+
+        Ok(tls)
+    }
+}
+
+impl TlsConfig {
+    /// Share link parameters describing this tls block, the inverse of
+    /// [`from_link`](Self::from_link)
+    fn link_params(&self) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+        let mut push = |key: &str, value: Option<&str>| {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                params.push((key.to_string(), value.to_string()));
             }
-        } 
+        };
 
-        if let Some(fp) = params.get("fp") {
-            if let Some(s) = fp.as_str() {
-                tls.utls = Some(UtlsConfig {
-                    enabled: true,
-                    fingerprint: s.to_string(),
-                });
+        if !self.enabled {
+            push("security", Some("none"));
+            return params;
+        }
+        push("security", Some(if self.reality.is_some() { "reality" } else { "tls" }));
+        push("sni", self.server_name.as_deref());
+        push("fp", self.utls.as_ref().map(|utls| utls.fingerprint.as_str()));
+        match &self.reality {
+            Some(reality) => {
+                push("pbk", reality.public_key.as_deref());
+                push("sid", Some(&reality.short_id));
+            }
+            None => {
+                push("alpn", self.alpn.as_ref().map(|alpn| alpn.join(",")).as_deref());
+                if self.insecure == Some(true) {
+                    push("allowInsecure", Some("1"));
+                }
             }
         }
+        params
+    }
+}
+
+/// Parameters of a plain tcp or V2Ray transport
+fn transport_link_params(transport: &Option<V2RayTransport>) -> Vec<(String, String)> {
+    match transport {
+        Some(transport) => transport.link_params(),
+        None => vec![("type".to_string(), "tcp".to_string())],
+    }
+}
+
+/// The part of a share link every proxy shares: scheme, user-info, server and tag
+fn base_link(scheme: &str, user_info: String, server: &str, server_port: i32, tag: &str) -> Result<ShareLink, Error> {
+    let port = u16::try_from(server_port).ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| Error::invalid_link("server_port", format!("{} is not a valid port", server_port)))?;
+    Ok(ShareLink {
+        scheme: scheme.to_string(),
+        user_info: Some(user_info),
+        host: server.parse::<Host>()?,
+        port: Some(port),
+        path: String::new(),
+        query: Vec::new(),
+        fragment: Some(tag.to_string()).filter(|tag| !tag.is_empty()),
+    })
+}
+
+impl VlessOutbound {
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let mut link = base_link("vless", self.uuid.clone(), &self.server, self.server_port, &self.tag)?;
+        link.query.push(("encryption".to_string(), "none".to_string()));
+        if let Some(tls) = &self.tls {
+            link.query.extend(tls.link_params());
+        }
+        link.query.extend(transport_link_params(&self.transport));
+        if let Some(flow) = &self.flow {
+            link.query.push(("flow".to_string(), flow.clone()));
+        }
+        if let Some(packet_encoding) = &self.packet_encoding {
+            link.query.push(("packetEncoding".to_string(), packet_encoding.clone()));
+        }
+        Ok(link)
+    }
+
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        (self.server, self.server_port) = link_server(link)?;
+        self.uuid = link_credentials(link, "uuid", "vless links must carry the uuid as user-info")?;
+        let tls = TlsConfig::merge_link(link, "none", self.tls.take())?;
+
+        self.transport = V2RayTransport::from_link(link)?;
+        self.extra.remove("transport");
+
+        self.flow = match link.param("flow").unwrap_or_default() {
+            "" | "none" => None,
+            flow @ "xtls-rprx-vision" => {
+                if let Some(transport) = &self.transport {
+                    return Err(Error::invalid_link("flow", format!(
+                        "{} only works over the raw tcp transport, not {}", flow, transport.type_name())));
+                }
+                if !tls.enabled {
+                    return Err(Error::invalid_link("flow", format!("{} requires security=tls or security=reality", flow)));
+                }
+                Some(flow.to_string())
+            }
+            other => return Err(Error::invalid_link("flow", format!("unsupported flow {:?}", other))),
+        };
+
+        if let Some(packet_encoding) = link.param("packetEncoding") {
+            self.packet_encoding = match packet_encoding {
+                "" | "none" => None,
+                "packetaddr" | "xudp" => Some(packet_encoding.to_string()),
+                other => return Err(Error::invalid_link("packetEncoding", format!("unsupported packet encoding {:?}", other))),
+            };
+        }
 
         self.tls = Some(tls);
+        Ok(())
     }
 }
 
-impl SingBoxConfig {
-    pub fn from_file(path: String) -> Option<SingBoxConfig> {
-        let file = File::open(path).expect("Failed to open sing-box config");
-       serde_json::from_reader(file).expect("Failed to parse sing-box json file")
+/// Ciphers sing-box accepts in a vmess outbound's `security`
+const VMESS_CIPHERS: &[&str] = &["auto", "none", "zero", "aes-128-gcm", "chacha20-poly1305", "aes-128-ctr"];
+
+impl VmessOutbound {
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let mut link = base_link("vmess", self.uuid.clone(), &self.server, self.server_port, &self.tag)?;
+        if let Some(security) = &self.security {
+            link.query.push(("encryption".to_string(), security.clone()));
+        }
+        if let Some(alter_id) = self.alter_id {
+            link.query.push(("aid".to_string(), alter_id.to_string()));
+        }
+        if let Some(tls) = &self.tls {
+            link.query.extend(tls.link_params());
+        }
+        link.query.extend(transport_link_params(&self.transport));
+        Ok(link)
     }
 
-    fn get_vless_outbound(&mut self) -> VlessOutbound {
-        let existing_out = self.outbounds.iter().find(|out| match out {
-            Outbound::Vless(_) => true,
-            _ => false
-        });
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        (self.server, self.server_port) = link_server(link)?;
+        self.uuid = link_credentials(link, "id", "vmess links must carry the user id")?;
 
-        return match existing_out {
-            Some(out) => match out {
-                Outbound::Vless(vless) => vless.clone(),
-                _ => unreachable!(),
-            },
-            None => {
-                let default_out = VlessOutbound::default();
-                self.outbounds.push(Outbound::Vless(default_out.clone()));
-                default_out
+        self.security = match link.param("encryption").or(link.param("scy")).unwrap_or("auto") {
+            "" => Some("auto".to_string()),
+            cipher if VMESS_CIPHERS.contains(&cipher) => Some(cipher.to_string()),
+            other => return Err(Error::invalid_link("scy", format!("unsupported vmess cipher {:?}", other))),
+        };
+        self.alter_id = match link.param("aid").unwrap_or_default() {
+            "" | "0" => None,
+            aid => Some(aid.parse::<i32>()
+                .map_err(|_| Error::invalid_link("aid", format!("invalid alter id {:?}", aid)))?),
+        };
+
+        self.tls = Some(TlsConfig::merge_link(link, "none", self.tls.take())?);
+
+        self.transport = V2RayTransport::from_link(link)?;
+        self.extra.remove("transport");
+
+        Ok(())
+    }
+}
+
+impl TrojanOutbound {
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let mut link = base_link("trojan", self.password.clone(), &self.server, self.server_port, &self.tag)?;
+        // Trojan defaults to tls; a missing block means none
+        match &self.tls {
+            Some(tls) => link.query.extend(tls.link_params()),
+            None => link.query.push(("security".to_string(), "none".to_string())),
+        }
+        link.query.extend(transport_link_params(&self.transport));
+        Ok(link)
+    }
+
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        (self.server, self.server_port) = link_server(link)?;
+        self.password = link_credentials(link, "password", "trojan links must carry the password as user-info")?;
+        self.tls = Some(TlsConfig::merge_link(link, "tls", self.tls.take())?);
+
+        self.transport = V2RayTransport::from_link(link)?;
+        self.extra.remove("transport");
+
+        Ok(())
+    }
+}
+
+/// Ciphers sing-box accepts whose password is a free-form string
+const SHADOWSOCKS_CIPHERS: &[&str] = &[
+    "none", "aes-128-gcm", "aes-192-gcm", "aes-256-gcm", "chacha20-ietf-poly1305", "xchacha20-ietf-poly1305",
+    "aes-128-ctr", "aes-192-ctr", "aes-256-ctr", "aes-128-cfb", "aes-192-cfb", "aes-256-cfb",
+    "rc4-md5", "chacha20-ietf", "xchacha20",
+];
+
+/// Shadowsocks 2022 ciphers with their key length; the password is a base64 key
+const SHADOWSOCKS_2022_CIPHERS: &[(&str, usize)] = &[
+    ("2022-blake3-aes-128-gcm", 16),
+    ("2022-blake3-aes-256-gcm", 32),
+    ("2022-blake3-chacha20-poly1305", 32),
+];
+
+/// Checks the password against what the cipher requires. 2022 AES ciphers take
+/// `iPSK:uPSK` chains for multi-user servers, each a base64 key of the cipher's length.
+fn validate_shadowsocks_password(method: &str, password: &str) -> Result<(), Error> {
+    if let Some((_, key_len)) = SHADOWSOCKS_2022_CIPHERS.iter().find(|(name, _)| *name == method) {
+        let keys: Vec<&str> = password.split(':').collect();
+        if keys.len() > 1 && method == "2022-blake3-chacha20-poly1305" {
+            return Err(Error::invalid_link("password", format!("{} does not support multi-user keys", method)));
+        }
+        for key in keys {
+            match decode_base64(key) {
+                Some(decoded) if decoded.len() == *key_len => {}
+                Some(decoded) => return Err(Error::invalid_link("password", format!(
+                    "{} needs a {}-byte key, got {} bytes", method, key_len, decoded.len()))),
+                None => return Err(Error::invalid_link("password", format!("{} keys must be base64", method))),
             }
+        }
+        return Ok(());
+    }
+
+    if !SHADOWSOCKS_CIPHERS.contains(&method) {
+        return Err(Error::invalid_link("method", format!("unsupported shadowsocks cipher {:?}", method)));
+    }
+    if password.is_empty() && method != "none" {
+        return Err(Error::invalid_link("password", format!("{} needs a password", method)));
+    }
+    Ok(())
+}
+
+impl ShadowsocksOutbound {
+    /// SIP002 puts 2022 keys in the clear and base64url encodes the other user-infos
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let user_info = format!("{}:{}", self.method, self.password);
+        let user_info = if self.method.starts_with("2022-") {
+            user_info
+        } else {
+            URL_SAFE_NO_PAD.encode(user_info)
         };
+        let mut link = base_link("ss", user_info, &self.server, self.server_port, &self.tag)?;
+        if let Some(plugin) = &self.plugin {
+            let plugin = match &self.plugin_opts {
+                Some(opts) => format!("{};{}", plugin, opts),
+                None => plugin.clone(),
+            };
+            link.query.push(("plugin".to_string(), plugin));
+        }
+        Ok(link)
     }
 
-    fn update_vless_outbound(&mut self, vless: VlessOutbound) {
-        let vless_out_idx: usize = self.outbounds.iter().position(|out| matches!(out, Outbound::Vless(_))).unwrap();
-        assert!(vless_out_idx > 0, "Vless outbound is not present");
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        (self.server, self.server_port) = link_server(link)?;
+
+        // SIP002 user-info is either `method:password` or base64url of it
+        let user_info = link_credentials(link, "userinfo", "ss links must carry method and password")?;
+        let user_info = if user_info.contains(':') {
+            user_info
+        } else {
+            decode_base64(&user_info)
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .ok_or_else(|| Error::invalid_link("userinfo", "expected method:password or its base64"))?
+        };
+        let (method, password) = user_info.split_once(':')
+            .ok_or_else(|| Error::invalid_link("userinfo", "expected method:password"))?;
+        let method = method.to_lowercase();
+        validate_shadowsocks_password(&method, password)?;
+        self.method = method;
+        self.password = password.to_string();
 
-        self.outbounds[vless_out_idx] = Outbound::Vless(vless);
+        // plugin=name;opt=value;... with the options handed to sing-box verbatim
+        self.plugin = None;
+        self.plugin_opts = None;
+        if let Some(plugin) = link.param("plugin").filter(|plugin| !plugin.is_empty()) {
+            let (name, opts) = plugin.split_once(';').unwrap_or((plugin, ""));
+            let name = match name {
+                "obfs-local" | "simple-obfs" => "obfs-local",
+                "v2ray-plugin" => "v2ray-plugin",
+                other => return Err(Error::invalid_link("plugin", format!("unsupported plugin {:?}", other))),
+            };
+            self.plugin = Some(name.to_string());
+            self.plugin_opts = Some(opts.to_string()).filter(|opts| !opts.is_empty());
+        }
+
+        Ok(())
     }
-    
-    pub fn enrich_from_url(&mut self, url: String) -> Result<Self, Box<dyn Error>> {
-        let params: Map<String, Value> = parse_url(&url)?;
-        let protocol = params.get("protocol").expect("Failed to parse protocol").as_str().expect("Failed to get protocol from URL");
-        let _outbound = match protocol {
-            "vless" => {
-                let mut vless = self.get_vless_outbound();
-                vless.enrich(params);
-                self.update_vless_outbound(vless); 
-            } 
-            _ => {
-                println!("Unknown protocol: {:?}", protocol);
-                todo!()
+}
+
+/// TLS of the QUIC based protocols: always on, never Reality, no uTLS
+fn quic_tls_from_link(link: &ShareLink, template: Option<TlsConfig>) -> Result<TlsConfig, Error> {
+    let mut tls = TlsConfig::merge_link(link, "tls", template)?;
+    if !tls.enabled || tls.reality.is_some() {
+        return Err(Error::UnsupportedSecurity(format!("{} requires plain tls", link.scheme)));
+    }
+    tls.utls = None;
+    Ok(tls)
+}
+
+/// Turns `mport=443,20000-30000` into sing-box `server_ports` ranges
+fn parse_port_hopping(mport: &str) -> Result<Vec<String>, Error> {
+    let parse = |port: &str| port.trim().parse::<u16>().ok().filter(|port| *port != 0);
+    mport.split(',')
+        .filter(|range| !range.trim().is_empty())
+        .map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            match (parse(start), parse(end)) {
+                (Some(start), Some(end)) if start <= end => Ok(format!("{}:{}", start, end)),
+                _ => Err(Error::invalid_link("mport", format!("invalid port range {:?}", range))),
             }
+        })
+        .collect()
+}
+
+/// Parameters of the tls block of a QUIC based protocol, whose links carry no `security`
+fn quic_tls_link_params(tls: &Option<TlsConfig>, insecure_key: &str) -> Vec<(String, String)> {
+    tls.iter()
+        .flat_map(TlsConfig::link_params)
+        .filter(|(key, _)| key != "security")
+        .map(|(key, value)| match key.as_str() {
+            "allowInsecure" => (insecure_key.to_string(), value),
+            _ => (key, value),
+        })
+        .collect()
+}
+
+impl Hysteria2Outbound {
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let mut link = base_link("hysteria2", self.password.clone(), &self.server, self.server_port, &self.tag)?;
+        link.query.extend(quic_tls_link_params(&self.tls, "insecure"));
+        if let Some(obfs) = &self.obfs {
+            link.query.push(("obfs".to_string(), obfs.type_field.clone()));
+            link.query.push(("obfs-password".to_string(), obfs.password.clone()));
+        }
+        if let Some(server_ports) = self.server_ports.as_ref().filter(|ports| !ports.is_empty()) {
+            let mport: Vec<String> = server_ports.iter().map(|range| range.replace(':', "-")).collect();
+            link.query.push(("mport".to_string(), mport.join(",")));
+        }
+        Ok(link)
+    }
+
+    /// `pinSHA256` pins the certificate hash, which sing-box cannot check. It is
+    /// left out, as [`Outbound::dropped_params`] reports, when the certificate is
+    /// verified normally, but refused together with `insecure=1` where the pin would
+    /// have been the only protection.
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        self.server = link.host.to_string();
+        // The Hysteria 2 URI scheme makes the port optional
+        self.server_port = match link.port.unwrap_or(443) {
+            0 => return Err(Error::invalid_link("port", "a non-zero port is required")),
+            port => port as i32,
+        };
+        self.password = link.user_info.clone()
+            .or(link.param("auth").map(str::to_string))
+            .filter(|password| !password.is_empty())
+            .ok_or_else(|| Error::invalid_link("auth", "hysteria2 links must carry the auth password"))?;
+
+        self.server_ports = match link.param("mport").filter(|mport| !mport.is_empty()) {
+            Some(mport) => Some(parse_port_hopping(mport)?),
+            None => None,
+        };
+
+        self.obfs = match link.param("obfs").unwrap_or_default() {
+            "" | "none" => None,
+            "salamander" => Some(Hysteria2Obfs {
+                type_field: "salamander".to_string(),
+                password: link.param("obfs-password")
+                    .filter(|password| !password.is_empty())
+                    .ok_or_else(|| Error::invalid_link("obfs-password", "salamander obfuscation needs a password"))?
+                    .to_string(),
+                extra: Map::new(),
+            }),
+            other => return Err(Error::invalid_link("obfs", format!("unsupported obfuscation {:?}", other))),
         };
-         
+
+        let tls = quic_tls_from_link(link, self.tls.take())?;
+        if let Some(pin) = link.param("pinSHA256").filter(|pin| !pin.is_empty()) {
+            let hex: String = pin.chars().filter(|c| *c != ':').collect();
+            if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::invalid_link("pinSHA256", format!("expected a hex SHA-256, got {:?}", pin)));
+            }
+            if tls.insecure == Some(true) {
+                return Err(Error::invalid_link("pinSHA256",
+                    "sing-box cannot pin certificates; drop insecure=1 or trust the server certificate"));
+            }
+        }
+        self.tls = Some(tls);
+
+        Ok(())
+    }
+}
+
+impl TuicOutbound {
+    fn to_link(&self) -> Result<ShareLink, Error> {
+        let user_info = format!("{}:{}", self.uuid, self.password);
+        let mut link = base_link("tuic", user_info, &self.server, self.server_port, &self.tag)?;
+        if let Some(congestion_control) = &self.congestion_control {
+            link.query.push(("congestion_control".to_string(), congestion_control.clone()));
+        }
+        if let Some(udp_relay_mode) = &self.udp_relay_mode {
+            link.query.push(("udp_relay_mode".to_string(), udp_relay_mode.clone()));
+        }
+        link.query.extend(quic_tls_link_params(&self.tls, "allow_insecure"));
+        Ok(link)
+    }
+
+    fn enrich(&mut self, link: &ShareLink) -> Result<(), Error> {
+        (self.server, self.server_port) = link_server(link)?;
+
+        let user_info = link.user_info.clone().unwrap_or_default();
+        let (uuid, password) = user_info.split_once(':').unwrap_or((&user_info, ""));
+        if uuid.is_empty() {
+            return Err(Error::invalid_link("uuid", "tuic links must carry uuid:password as user-info"));
+        }
+        self.uuid = uuid.to_string();
+        self.password = password.to_string();
+
+        if let Some(congestion_control) = link.param("congestion_control").filter(|cc| !cc.is_empty()) {
+            self.congestion_control = match congestion_control {
+                "cubic" | "new_reno" | "bbr" => Some(congestion_control.to_string()),
+                other => return Err(Error::invalid_link("congestion_control", format!("unsupported congestion control {:?}", other))),
+            };
+        }
+        if let Some(udp_relay_mode) = link.param("udp_relay_mode").filter(|mode| !mode.is_empty()) {
+            self.udp_relay_mode = match udp_relay_mode {
+                "native" | "quic" => Some(udp_relay_mode.to_string()),
+                other => return Err(Error::invalid_link("udp_relay_mode", format!("unsupported udp relay mode {:?}", other))),
+            };
+        }
+
+        self.tls = Some(quic_tls_from_link(link, self.tls.take())?);
+
+        Ok(())
+    }
+}
+
+impl SingBoxConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<SingBoxConfig, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|source| Error::TemplateIo { path: path.to_path_buf(), source })?;
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|source| Error::TemplateSchema { path: path.to_path_buf(), source })
+    }
+
+    /// [`from_file`](Self::from_file) that reads the outbounds with
+    /// [`Outbound::from_value_strict`], so a missing or ill-typed field anywhere in the
+    /// config is an error naming its path
+    pub fn from_file_strict(path: impl AsRef<Path>) -> Result<SingBoxConfig, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|source| Error::TemplateIo { path: path.to_path_buf(), source })?;
+        let value: Value = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|source| Error::TemplateSchema { path: path.to_path_buf(), source })?;
+        Self::from_value_strict(value)
+    }
+
+    /// Reads a config the way [`from_file_strict`](Self::from_file_strict) does
+    pub fn from_value_strict(value: Value) -> Result<SingBoxConfig, Error> {
+        for (i, outbound) in value.get("outbounds").and_then(Value::as_array).into_iter().flatten().enumerate() {
+            let checked = match outbound {
+                Value::Object(map) => Outbound::from_map(map.clone(), true).map(drop),
+                _ => Err(FieldError::new("", "an outbound must be an object")),
+            };
+            checked.map_err(|e| e.within(&format!("outbounds[{}]", i)))?;
+        }
+        Ok(from_value_at(value)?)
+    }
+
+    pub fn outbounds(&self) -> &[Outbound] {
+        &self.outbounds
+    }
+
+    pub fn route_rules(&self) -> &[RouteRule] {
+        &self.route.rules
+    }
+
+    /// `route.final`, where traffic no rule matches goes
+    pub fn route_final(&self) -> Option<&str> {
+        self.route.final_field.as_deref()
+    }
+
+    /// Where traffic no rule matches goes, and DNS servers without a detour connect
+    /// through: `route.final`, or the first outbound
+    pub fn default_outbound(&self) -> Option<&str> {
+        self.route_final().or(self.outbounds.first().map(Outbound::tag))
+    }
+
+    pub fn enrich_from_url(&mut self, url: String) -> Result<Self, Error> {
+        let link = parse_share_link(&url)?;
+        let type_name = outbound_type(&link.scheme)
+            .ok_or_else(|| Error::UnsupportedProtocol(link.scheme.clone()))?;
+
+        // The first outbound of the same protocol is the template for the new one
+        let idx = self.outbounds.iter().position(|out| out.type_name() == type_name);
+        let outbound = Outbound::from_link(&link, idx.map(|i| &self.outbounds[i]))?;
+        match idx {
+            Some(i) => self.outbounds[i] = outbound,
+            None => self.outbounds.push(outbound),
+        }
+
         Ok(self.clone())
     }
 
-    pub fn enrich_from_dns(&mut self, dns: String) -> Result<Self, Box<dyn Error>> {
+    /// Replaces the proxy outbounds of the template with one outbound per link.
+    /// Each template proxy serves as the base for links of its protocol, tags come
+    /// from the link names and are made unique. Links that fail are reported and
+    /// skipped; if none succeed the config is left untouched.
+    pub fn import_links(&mut self, links: &[String]) -> ImportReport {
+        let links = links.iter()
+            .map(|url| (url.clone(), parse_share_link(url)))
+            .collect();
+        self.import_parsed(links)
+    }
+
+    /// [`import_links`](Self::import_links) over links that are already parsed, each
+    /// with the text the report shows for it
+    pub(crate) fn import_parsed(&mut self, links: Vec<(String, Result<ShareLink, Error>)>) -> ImportReport {
+        let mut report = ImportReport::default();
+        // Links can't be named like the groups add_groups puts them behind
+        let mut tags: Vec<String> = GROUP_TAGS.iter()
+            .map(|tag| tag.to_string())
+            .chain(self.outbounds.iter().filter(|out| !out.is_proxy()).map(|out| out.tag().to_string()))
+            .collect();
+        let mut imported = Vec::new();
+
+        for (i, (source, link)) in links.into_iter().enumerate() {
+            let dropped = link.as_ref().map(Outbound::dropped_params).unwrap_or_default();
+            let outbound = link.and_then(|link| {
+                let type_name = outbound_type(&link.scheme)
+                    .ok_or_else(|| Error::UnsupportedProtocol(link.scheme.clone()))?;
+                let template = self.outbounds.iter().find(|out| out.type_name() == type_name);
+                let mut outbound = Outbound::from_link(&link, template)?;
+                outbound.set_tag(unique_tag(&link_tag(&link), &tags));
+                Ok(outbound)
+            });
+
+            match outbound {
+                Ok(outbound) => {
+                    tags.push(outbound.tag().to_string());
+                    report.imported.push(outbound.tag().to_string());
+                    imported.push(outbound);
+                    report.dropped.extend(dropped.into_iter().map(|(param, reason)| DroppedParam {
+                        line: i + 1,
+                        param: param.to_string(),
+                        reason: reason.to_string(),
+                    }));
+                }
+                Err(reason) => report.skipped.push(SkippedLink { line: i + 1, link: source, reason }),
+            }
+        }
+
+        if !imported.is_empty() {
+            self.replace_proxies(imported);
+        }
+        report
+    }
+
+    /// Swaps the template's proxy outbounds for `imported`. References to the
+    /// replaced proxies from `route` and from DNS server and outbound `detour`s now
+    /// go to the first imported one, and groups that offered them offer all
+    /// imported ones.
+    fn replace_proxies(&mut self, imported: Vec<Outbound>) {
+        let removed: Vec<String> = self.outbounds.iter()
+            .filter(|out| out.is_proxy())
+            .map(|out| out.tag().to_string())
+            .collect();
+        let tags: Vec<String> = imported.iter().map(|out| out.tag().to_string()).collect();
+        let first = tags[0].clone();
+        let repoint = |tag: &mut Option<String>| {
+            if tag.as_ref().is_some_and(|tag| removed.contains(tag)) {
+                *tag = Some(first.clone());
+            }
+        };
+        repoint(&mut self.route.final_field);
+        for rule in self.route.rules.iter_mut() {
+            repoint(&mut rule.outbound);
+        }
+        let detours = self.dns.servers.iter_mut().map(|server| &mut server.extra)
+            .chain(self.outbounds.iter_mut().map(Outbound::extra_mut));
+        for extra in detours {
+            if extra.get("detour").and_then(Value::as_str).is_some_and(|tag| removed.iter().any(|removed| removed == tag)) {
+                extra.insert("detour".to_string(), Value::String(first.clone()));
+            }
+        }
+        // Groups of the template that offered the replaced proxies offer the imported ones
+        for out in self.outbounds.iter_mut() {
+            let members = match out {
+                Outbound::Selector(selector) => {
+                    if selector.default.as_ref().is_some_and(|tag| removed.contains(tag)) {
+                        selector.default = None;
+                    }
+                    &mut selector.outbounds
+                }
+                Outbound::UrlTest(urltest) => &mut urltest.outbounds,
+                _ => continue,
+            };
+            if members.iter().any(|tag| removed.contains(tag)) {
+                members.retain(|tag| !removed.contains(tag));
+                members.extend(tags.iter().cloned());
+            }
+        }
+
+        self.outbounds.retain(|out| !out.is_proxy());
+        self.outbounds.extend(imported);
+    }
+
+    /// Replaces the proxy outbounds of the template with ready-made ones, e.g. from
+    /// [`decode_json_subscription`](crate::subscription::decode_json_subscription), the
+    /// way [`import_links`](Self::import_links) does. A server and port listed twice is
+    /// imported once, a colliding tag gets a `-2`, `-3`, ... suffix. The non-proxy
+    /// outbounds are kept; if nothing can be imported the config is left untouched.
+    pub fn import_outbounds(&mut self, outbounds: Vec<ImportEntry>) -> ImportReport {
+        let kept: Vec<&Outbound> = self.outbounds.iter().filter(|out| !out.is_proxy()).collect();
+        let (imported, report) = dedupe_entries(outbounds, &kept);
+        if !imported.is_empty() {
+            self.replace_proxies(imported);
+        }
+        report
+    }
+
+    /// Adds ready-made proxy outbounds next to those already in the config, e.g. after
+    /// [`import_links`](Self::import_links). An outbound whose server and port are
+    /// already in the config is skipped, a colliding tag gets a `-2`, `-3`, ... suffix.
+    /// Nothing else is changed.
+    pub fn merge_outbounds(&mut self, outbounds: Vec<ImportEntry>) -> ImportReport {
+        let existing: Vec<&Outbound> = self.outbounds.iter().collect();
+        let (imported, report) = dedupe_entries(outbounds, &existing);
+        self.outbounds.extend(imported);
+        report
+    }
+
+    /// Replaces the proxy outbounds with the `proxies` of a Clash profile the way
+    /// [`import_links`](Self::import_links) does. With `with_groups` the profile's
+    /// `select` groups become selectors and its `url-test` and `fallback` groups
+    /// urltests, and `route.final` points at the first of them. Group members that
+    /// were not imported, and `REJECT`, are left out; `DIRECT` maps to the direct outbound.
+    pub fn import_clash(&mut self, profile: &ClashProfile, with_groups: bool) -> ClashImport {
+        let links = profile.proxies.iter()
+            .map(|proxy| (proxy_name(proxy).unwrap_or_default(), proxy_to_link(proxy)))
+            .collect();
+        let mut import = ClashImport { proxies: self.import_parsed(links), ..Default::default() };
+        if !with_groups || import.proxies.imported.is_empty() {
+            return import;
+        }
+
+        // Clash refers to proxies and groups by name
+        let mut names: HashMap<String, String> = HashMap::new();
+        let mut imported = import.proxies.imported.iter();
+        for (i, proxy) in profile.proxies.iter().enumerate() {
+            if import.proxies.skipped.iter().any(|skipped| skipped.line == i + 1) {
+                continue;
+            }
+            if let (Some(name), Some(tag)) = (proxy_name(proxy), imported.next()) {
+                names.insert(name, tag.clone());
+            }
+        }
+        if let Some(direct) = self.outbounds.iter().find(|out| matches!(out, Outbound::Direct(_))) {
+            names.insert("DIRECT".to_string(), direct.tag().to_string());
+        }
+
+        let mut taken: Vec<String> = self.outbounds.iter().map(|out| out.tag().to_string()).collect();
+        let mut groups = Vec::new();
+        for group in &profile.proxy_groups {
+            if !matches!(group.kind.as_str(), "select" | "url-test" | "fallback") {
+                import.skipped_groups.push(SkippedGroup {
+                    name: group.name.clone(),
+                    reason: format!("sing-box has no {} group", group.kind),
+                });
+                continue;
+            }
+            let tag = unique_tag(&group.name, &taken);
+            taken.push(tag.clone());
+            names.insert(group.name.clone(), tag.clone());
+            groups.push((group, tag));
+        }
+
+        // Dropping a group that has no members left can empty the groups that offered it
+        let empty = |names: &HashMap<String, String>, group: &ClashGroup| !group.proxies.iter().any(|name| names.contains_key(name));
+        while let Some(i) = groups.iter().position(|(group, _)| empty(&names, group)) {
+            let (group, _) = groups.remove(i);
+            names.remove(&group.name);
+            import.skipped_groups.push(SkippedGroup {
+                name: group.name.clone(),
+                reason: "none of its members were imported".to_string(),
+            });
+        }
+
+        for (group, tag) in groups {
+            let outbounds: Vec<String> = group.proxies.iter().filter_map(|name| names.get(name).cloned()).collect();
+            let outbound = match group.kind.as_str() {
+                "select" => Outbound::Selector(SelectorOutbound { outbounds, tag: tag.clone(), ..Default::default() }),
+                // sing-box has no ordered failover; a urltest is the closest match
+                _ => Outbound::UrlTest(UrlTestOutbound {
+                    outbounds,
+                    url: group.url.clone(),
+                    interval: group.interval.map(|seconds| format!("{}s", seconds)),
+                    tolerance: group.tolerance,
+                    tag: tag.clone(),
+                    ..Default::default()
+                }),
+            };
+            self.put_outbound(outbound);
+            import.groups.push(tag);
+        }
+        if let Some(first) = import.groups.first() {
+            self.route.final_field = Some(first.clone());
+        }
+
+        import
+    }
+
+    /// Puts every proxy outbound behind the groups `options` asks for and points
+    /// `route.final` at the outermost one. Groups already in the template under the
+    /// same tag are updated in place, keeping their other settings; a template
+    /// outbound of another type keeps the tag and the group gets a unique one.
+    pub fn add_groups(&mut self, options: &GroupOptions) {
+        let proxies: Vec<String> = self.outbounds.iter()
+            .filter(|out| out.is_proxy())
+            .map(|out| out.tag().to_string())
+            .collect();
+        if proxies.is_empty() || options.strategy == GroupStrategy::None {
+            return;
+        }
+
+        let mut urltest_tag = URLTEST_TAG.to_string();
+        if matches!(options.strategy, GroupStrategy::UrlTest | GroupStrategy::Both) {
+            let mut urltest = match self.outbounds.iter().find(|out| out.tag() == URLTEST_TAG) {
+                Some(Outbound::UrlTest(urltest)) => urltest.clone(),
+                _ => UrlTestOutbound { tag: URLTEST_TAG.to_string(), ..Default::default() },
+            };
+            urltest.outbounds = proxies.clone();
+            urltest.url = options.url.clone().or(urltest.url);
+            urltest.interval = options.interval.clone().or(urltest.interval);
+            urltest.tolerance = options.tolerance.or(urltest.tolerance);
+            urltest_tag = self.put_outbound(Outbound::UrlTest(urltest));
+        }
+
+        let mut selector_tag = SELECTOR_TAG.to_string();
+        if matches!(options.strategy, GroupStrategy::Selector | GroupStrategy::Both) {
+            let mut selector = match self.outbounds.iter().find(|out| out.tag() == SELECTOR_TAG) {
+                Some(Outbound::Selector(selector)) => selector.clone(),
+                _ => SelectorOutbound { tag: SELECTOR_TAG.to_string(), ..Default::default() },
+            };
+            selector.outbounds = proxies.clone();
+            selector.default = None;
+            if options.strategy == GroupStrategy::Both {
+                selector.outbounds.insert(0, urltest_tag.clone());
+                selector.default = Some(urltest_tag.clone());
+            }
+            selector_tag = self.put_outbound(Outbound::Selector(selector));
+        }
+
+        let group = match options.strategy {
+            GroupStrategy::UrlTest => urltest_tag,
+            _ => selector_tag,
+        };
+        self.route.final_field = Some(group);
+    }
+
+    /// Replaces the group with the same tag, or appends the outbound. An outbound
+    /// that is not a group keeps its tag, and the new one gets a unique one instead.
+    /// Returns the tag the outbound was put under.
+    pub(crate) fn put_outbound(&mut self, mut outbound: Outbound) -> String {
+        let tag = outbound.tag().to_string();
+        match self.outbounds.iter().position(|out| out.tag() == tag) {
+            Some(i) if matches!(self.outbounds[i], Outbound::Selector(_) | Outbound::UrlTest(_)) => {
+                self.outbounds[i] = outbound;
+                tag
+            }
+            Some(_) => {
+                let taken: Vec<String> = self.outbounds.iter().map(|out| out.tag().to_string()).collect();
+                let tag = unique_tag(&tag, &taken);
+                outbound.set_tag(tag.clone());
+                self.outbounds.push(outbound);
+                tag
+            }
+            None => {
+                self.outbounds.push(outbound);
+                tag
+            }
+        }
+    }
+
+    pub fn enrich_from_dns(&mut self, dns: String) -> Result<Self, Error> {
         let non_local_server = self.dns.servers.iter_mut().find(|s| s.address != "local");
         if let Some(server) = non_local_server {
             server.address = dns;
         } else {
-            let mut new_dns = DnsServer::default();
-            new_dns.address = dns;
+            let new_dns = DnsServer {
+                address: dns,
+                ..Default::default()
+            };
             self.dns.servers.push(new_dns);
         }
         Ok(self.clone())
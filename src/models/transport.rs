@@ -0,0 +1,238 @@
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::url_parser::ShareLink;
+
+
+/// Header sing-box (and Xray) put the ws early data into
+pub const EARLY_DATA_HEADER_NAME: &str = "Sec-WebSocket-Protocol";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HttpTransport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) host: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WsTransport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) headers: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_early_data: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    early_data_header_name: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GrpcTransport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) service_name: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HttpUpgradeTransport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    headers: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QuicTransport {
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// XHTTP is not in upstream sing-box; the shape follows the forks that ship it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct XhttpTransport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) mode: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// sing-box `transport` object of V2Ray based outbounds
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum V2RayTransport {
+    Http(HttpTransport),
+    Ws(WsTransport),
+    Grpc(GrpcTransport),
+    #[serde(rename = "httpupgrade")]
+    HttpUpgrade(HttpUpgradeTransport),
+    Quic(QuicTransport),
+    Xhttp(XhttpTransport),
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+fn host_header(host: Option<&str>) -> Option<Map<String, Value>> {
+    non_empty(host).map(|host| {
+        let mut headers = Map::new();
+        headers.insert("Host".to_string(), Value::String(host));
+        headers
+    })
+}
+
+/// Splits `?ed=N` off a ws path, the way Xray share links carry early data
+fn split_early_data(path: &str) -> Result<(String, Option<u32>), Error> {
+    let Some((base, query)) = path.split_once('?') else {
+        return Ok((path.to_string(), None));
+    };
+
+    let mut early_data = None;
+    let mut rest = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        match pair.split_once('=') {
+            Some(("ed", value)) => {
+                early_data = Some(value.parse::<u32>()
+                    .map_err(|_| Error::invalid_link("path", format!("invalid early data size {:?}", value)))?);
+            }
+            _ => rest.push(pair),
+        }
+    }
+
+    let path = if rest.is_empty() { base.to_string() } else { format!("{}?{}", base, rest.join("&")) };
+    Ok((path, early_data))
+}
+
+impl V2RayTransport {
+    /// Value of the `type` key
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            V2RayTransport::Http(_) => "http",
+            V2RayTransport::Ws(_) => "ws",
+            V2RayTransport::Grpc(_) => "grpc",
+            V2RayTransport::HttpUpgrade(_) => "httpupgrade",
+            V2RayTransport::Quic(_) => "quic",
+            V2RayTransport::Xhttp(_) => "xhttp",
+        }
+    }
+
+    /// Share link parameters describing this transport, the inverse of [`from_link`](Self::from_link)
+    pub fn link_params(&self) -> Vec<(String, String)> {
+        let mut params = vec![("type".to_string(), self.type_name().to_string())];
+        let mut push = |key: &str, value: Option<&str>| {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                params.push((key.to_string(), value.to_string()));
+            }
+        };
+        let header_host = |headers: &Option<Map<String, Value>>| {
+            headers.as_ref().and_then(|headers| headers.get("Host")).and_then(Value::as_str).map(str::to_string)
+        };
+
+        match self {
+            V2RayTransport::Http(http) => {
+                push("host", http.host.as_ref().map(|host| host.join(",")).as_deref());
+                push("path", http.path.as_deref());
+            }
+            V2RayTransport::Ws(ws) => {
+                push("host", header_host(&ws.headers).as_deref());
+                push("path", ws.path.as_deref());
+                push("ed", ws.max_early_data.map(|ed| ed.to_string()).as_deref());
+            }
+            V2RayTransport::Grpc(grpc) => push("serviceName", grpc.service_name.as_deref()),
+            V2RayTransport::HttpUpgrade(upgrade) => {
+                push("host", upgrade.host.as_deref());
+                push("path", upgrade.path.as_deref());
+            }
+            V2RayTransport::Quic(_) => {}
+            V2RayTransport::Xhttp(xhttp) => {
+                push("host", xhttp.host.as_deref());
+                push("path", xhttp.path.as_deref());
+                push("mode", xhttp.mode.as_deref());
+            }
+        }
+        params
+    }
+
+    /// Builds the transport described by a share link's `type`, `path`, `host`,
+    /// `serviceName`, `mode`, `headerType` and `ed` parameters. Plain tcp yields `None`.
+    pub fn from_link(link: &ShareLink) -> Result<Option<V2RayTransport>, Error> {
+        let host = link.param("host");
+        let path = non_empty(link.param("path"));
+
+        let transport = match link.param("type").unwrap_or("tcp") {
+            "" | "tcp" | "raw" => match link.param("headerType").unwrap_or("none") {
+                "" | "none" => None,
+                "http" => Some(V2RayTransport::Http(HttpTransport {
+                    host: non_empty(host).map(|h| h.split(',').map(|h| h.trim().to_string()).collect()),
+                    path,
+                    ..Default::default()
+                })),
+                other => return Err(Error::UnsupportedTransport(format!("tcp with headerType={}", other))),
+            },
+            "ws" | "websocket" => {
+                let (path, mut max_early_data) = match path {
+                    Some(path) => {
+                        let (path, ed) = split_early_data(&path)?;
+                        (non_empty(Some(&path)), ed)
+                    }
+                    None => (None, None),
+                };
+                if let Some(ed) = link.param("ed").filter(|ed| !ed.is_empty()) {
+                    max_early_data = Some(ed.parse::<u32>()
+                        .map_err(|_| Error::invalid_link("ed", format!("invalid early data size {:?}", ed)))?);
+                }
+                let max_early_data = max_early_data.filter(|ed| *ed > 0);
+                Some(V2RayTransport::Ws(WsTransport {
+                    path,
+                    headers: host_header(host),
+                    early_data_header_name: max_early_data.map(|_| EARLY_DATA_HEADER_NAME.to_string()),
+                    max_early_data,
+                    ..Default::default()
+                }))
+            }
+            "grpc" | "gun" => Some(V2RayTransport::Grpc(GrpcTransport {
+                service_name: non_empty(link.param("serviceName")),
+                ..Default::default()
+            })),
+            "httpupgrade" => Some(V2RayTransport::HttpUpgrade(HttpUpgradeTransport {
+                host: non_empty(host),
+                path,
+                ..Default::default()
+            })),
+            "http" | "h2" => Some(V2RayTransport::Http(HttpTransport {
+                host: non_empty(host).map(|h| h.split(',').map(|h| h.trim().to_string()).collect()),
+                path,
+                ..Default::default()
+            })),
+            "quic" => Some(V2RayTransport::Quic(QuicTransport::default())),
+            "xhttp" | "splithttp" => Some(V2RayTransport::Xhttp(XhttpTransport {
+                host: non_empty(host),
+                path,
+                mode: non_empty(link.param("mode")),
+                ..Default::default()
+            })),
+            other => return Err(Error::UnsupportedTransport(other.to_string())),
+        };
+
+        Ok(transport)
+    }
+}
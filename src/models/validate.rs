@@ -0,0 +1,133 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{Outbound, SingBoxConfig};
+
+
+/// A problem [`SingBoxConfig::validate`] found that would keep sing-box from starting
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// JSON path of the offending value, e.g. `route.rules[2].outbound`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Whether sing-box takes `cidr` as an `ip_cidr` entry: a prefix or a bare address
+fn valid_cidr(cidr: &str) -> bool {
+    let Some((address, prefix)) = cidr.split_once('/') else {
+        return cidr.parse::<IpAddr>().is_ok();
+    };
+    let max = match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit())
+        && prefix.parse::<u8>().is_ok_and(|prefix| prefix <= max)
+}
+
+fn check_port(port: i64, path: String, diagnostics: &mut Vec<Diagnostic>) {
+    if !(1..=65535).contains(&port) {
+        diagnostics.push(Diagnostic { path, message: format!("port {} is outside 1-65535", port) });
+    }
+}
+
+impl SingBoxConfig {
+    /// Checks what the models cannot: that tags are unique across inbounds and
+    /// outbounds, that `route.final`, rule outbounds and group members name an
+    /// outbound, that `dns.final` and `route.default_domain_resolver` name a DNS
+    /// server, that `ip_cidr` entries parse and that ports are within 1-65535.
+    /// An empty list means the config is consistent.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        // sing-box looks inbounds and outbounds up by tag, so a tag names one of either
+        let mut tags: Vec<(&str, String)> = Vec::new();
+        let inbounds = self.inbounds.iter().enumerate().map(|(i, inbound)| (inbound.tag.as_str(), format!("inbounds[{}]", i)));
+        let outbounds = self.outbounds.iter().enumerate().map(|(i, out)| (out.tag(), format!("outbounds[{}]", i)));
+        for (tag, path) in inbounds.chain(outbounds) {
+            if tag.is_empty() {
+                continue;
+            }
+            match tags.iter().find(|(taken, _)| *taken == tag) {
+                Some((_, first)) => diagnostics.push(Diagnostic {
+                    path: format!("{}.tag", path),
+                    message: format!("tag {:?} is already used by {}", tag, first),
+                }),
+                None => tags.push((tag, path)),
+            }
+        }
+
+        let outbound_exists = |tag: &str| self.outbounds.iter().any(|out| out.tag() == tag);
+        let mut check_outbound = |tag: &str, path: String| {
+            if !outbound_exists(tag) {
+                diagnostics.push(Diagnostic { path, message: format!("no outbound is tagged {:?}", tag) });
+            }
+        };
+        for (i, out) in self.outbounds.iter().enumerate() {
+            for (j, member) in out.members().iter().enumerate() {
+                check_outbound(member, format!("outbounds[{}].outbounds[{}]", i, j));
+            }
+            if let Outbound::Selector(selector) = out && let Some(default) = &selector.default {
+                check_outbound(default, format!("outbounds[{}].default", i));
+            }
+        }
+        if let Some(final_tag) = &self.route.final_field {
+            check_outbound(final_tag, "route.final".to_string());
+        }
+        for (i, rule) in self.route.rules.iter().enumerate() {
+            if let Some(outbound) = &rule.outbound {
+                check_outbound(outbound, format!("route.rules[{}].outbound", i));
+            }
+        }
+
+        let server_exists = |tag: &str| self.dns.servers.iter().any(|server| server.tag == tag);
+        if !server_exists(&self.dns.final_field) {
+            diagnostics.push(Diagnostic {
+                path: "dns.final".to_string(),
+                message: format!("no DNS server is tagged {:?}", self.dns.final_field),
+            });
+        }
+        if let Some(resolver) = &self.route.default_domain_resolver && !server_exists(resolver) {
+            diagnostics.push(Diagnostic {
+                path: "route.default_domain_resolver".to_string(),
+                message: format!("no DNS server is tagged {:?}", resolver),
+            });
+        }
+
+        for (i, rule) in self.route.rules.iter().enumerate() {
+            for (j, cidr) in rule.ip_cidr.iter().flatten().enumerate() {
+                if !valid_cidr(cidr) {
+                    diagnostics.push(Diagnostic {
+                        path: format!("route.rules[{}].ip_cidr[{}]", i, j),
+                        message: format!("{:?} is not an IP address or CIDR", cidr),
+                    });
+                }
+            }
+            if let Some(port) = rule.port {
+                check_port(i64::from(port), format!("route.rules[{}].port", i), &mut diagnostics);
+            }
+        }
+        for (i, inbound) in self.inbounds.iter().enumerate() {
+            if let Some(port) = inbound.extra.get("listen_port").and_then(Value::as_i64) {
+                check_port(port, format!("inbounds[{}].listen_port", i), &mut diagnostics);
+            }
+        }
+        for (i, out) in self.outbounds.iter().enumerate() {
+            if let Some((_, port)) = out.server() {
+                check_port(i64::from(port), format!("outbounds[{}].server_port", i), &mut diagnostics);
+            }
+        }
+
+        diagnostics
+    }
+}
@@ -0,0 +1,82 @@
+use image::{GrayImage, Luma};
+use qrcode::render::{svg, unicode};
+use qrcode::QrCode;
+use std::path::Path;
+
+use crate::error::Error;
+
+
+/// Smallest side of the PNG and SVG images, in pixels
+const IMAGE_SIZE: u32 = 320;
+
+/// Renders `data` as a QR code made of Unicode half-blocks, two rows of modules per
+/// line. Dark modules are left blank so the code scans off a dark terminal.
+pub fn render_terminal(data: &str) -> Result<String, Error> {
+    let code = QrCode::new(data.as_bytes()).map_err(Error::QrEncode)?;
+    Ok(code.render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// Writes `data` as a QR code image, PNG or SVG depending on the file extension
+pub fn write_image(data: &str, path: impl AsRef<Path>) -> Result<(), Error> {
+    let path = path.as_ref();
+    let code = QrCode::new(data.as_bytes()).map_err(Error::QrEncode)?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => code.render::<Luma<u8>>()
+            .min_dimensions(IMAGE_SIZE, IMAGE_SIZE)
+            .build()
+            .save(path)
+            .map_err(|source| Error::Image { path: path.to_path_buf(), source }),
+        "svg" => {
+            let svg = code.render::<svg::Color>()
+                .min_dimensions(IMAGE_SIZE, IMAGE_SIZE)
+                .build();
+            Ok(std::fs::write(path, svg)?)
+        }
+        _ => Err(Error::UnsupportedImageFormat(path.to_path_buf())),
+    }
+}
+
+/// Decodes every QR code in an image, top to bottom and left to right. Codes
+/// drawn light on dark, as some dark themes show them, are read as well.
+pub fn decode_luma(image: &GrayImage) -> Vec<String> {
+    let codes = decode_grids(image, false);
+    if codes.is_empty() {
+        decode_grids(image, true)
+    } else {
+        codes
+    }
+}
+
+fn decode_grids(image: &GrayImage, inverted: bool) -> Vec<String> {
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(image.width() as usize, image.height() as usize, |x, y| {
+        let Luma([luma]) = *image.get_pixel(x as u32, y as u32);
+        if inverted { 255 - luma } else { luma }
+    });
+    let mut codes: Vec<((i32, i32), String)> = prepared.detect_grids().iter()
+        .filter_map(|grid| {
+            let (_, content) = grid.decode().ok()?;
+            let top = grid.bounds.iter().map(|point| point.y).min()?;
+            let left = grid.bounds.iter().map(|point| point.x).min()?;
+            Some(((top, left), content))
+        })
+        .collect();
+    codes.sort_by_key(|(position, _)| *position);
+    codes.into_iter().map(|(_, content)| content).collect()
+}
+
+/// Decodes every QR code in a PNG or JPEG file, e.g. a screenshot of a panel
+pub fn decode_image(path: impl AsRef<Path>) -> Result<Vec<String>, Error> {
+    let path = path.as_ref();
+    let image = image::open(path)
+        .map_err(|source| Error::Image { path: path.to_path_buf(), source })?
+        .to_luma8();
+    let codes = decode_luma(&image);
+    if codes.is_empty() {
+        return Err(Error::NoQrCode(path.to_path_buf()));
+    }
+    Ok(codes)
+}
@@ -0,0 +1,146 @@
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::models::Outbound;
+use crate::url_parser::{decode_base64, Host, ParseError, ShareLink};
+
+
+/// A subscription line that could not be turned into an outbound
+#[derive(Debug)]
+pub struct SkippedLink {
+    /// 1-based position of the link among those handed to the import, which is
+    /// only its subscription line if the subscription was imported on its own
+    pub line: usize,
+    pub link: String,
+    pub reason: Error,
+}
+
+/// A parameter of an imported link that the outbound leaves out
+#[derive(Debug)]
+pub struct DroppedParam {
+    /// 1-based position of the link, counted as for [`SkippedLink`]
+    pub line: usize,
+    pub param: String,
+    pub reason: String,
+}
+
+/// An outbound to import, or why it cannot be, with the name a report shows for it
+pub type ImportEntry = (String, Result<Outbound, Error>);
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Tags of the outbounds created, in subscription order
+    pub imported: Vec<String>,
+    pub skipped: Vec<SkippedLink>,
+    /// Parameters of the imported links that sing-box has no option for
+    pub dropped: Vec<DroppedParam>,
+}
+
+/// Splits a subscription body into share links. Providers serve base64 of a
+/// newline separated list; an already decoded list is accepted as well.
+pub fn decode_subscription(body: &str) -> Result<Vec<String>, Error> {
+    let body = body.trim();
+    let decoded = if body.contains("://") {
+        body.to_string()
+    } else {
+        decode_base64(body)
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or_else(|| Error::Parse { input: "subscription".to_string(), kind: ParseError::InvalidBase64 })?
+    };
+
+    Ok(decoded.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether a subscription body is JSON rather than a list of links
+pub fn is_json_subscription(body: &str) -> bool {
+    body.trim_start().starts_with(['{', '['])
+}
+
+/// Outbounds of a JSON subscription: the servers of a SIP008 document, or the proxies
+/// of a sing-box `outbounds` array or full config. Each comes with the name a report
+/// shows for it. Direct, dns, block and group outbounds are left out.
+pub fn decode_json_subscription(body: &str) -> Result<Vec<ImportEntry>, Error> {
+    let json: Value = serde_json::from_str(body)?;
+    let (servers, outbounds) = match json {
+        Value::Array(outbounds) => (None, outbounds),
+        Value::Object(mut map) => match (map.remove("servers"), map.remove("outbounds")) {
+            (Some(Value::Array(servers)), _) => (Some(servers), Vec::new()),
+            (_, Some(Value::Array(outbounds))) => (None, outbounds),
+            _ => return Err(Error::InvalidSubscription(
+                "expected a SIP008 \"servers\" list or a sing-box \"outbounds\" list".to_string())),
+        },
+        _ => return Err(Error::InvalidSubscription("expected a JSON object or array".to_string())),
+    };
+
+    if let Some(servers) = servers {
+        return Ok(servers.iter()
+            .map(|server| {
+                let server = server.as_object().cloned().unwrap_or_default();
+                let name = sip008_field(&server, "remarks")
+                    .or_else(|| sip008_field(&server, "id"))
+                    .unwrap_or_default();
+                let outbound = sip008_link(&server).and_then(|link| Outbound::from_link(&link, None));
+                (name, outbound)
+            })
+            .collect());
+    }
+
+    Ok(outbounds.into_iter()
+        .filter_map(|outbound| {
+            let type_name = outbound.get("type").and_then(Value::as_str).unwrap_or_default().to_string();
+            let name = outbound.get("tag").and_then(Value::as_str).unwrap_or_default().to_string();
+            let outbound = match type_name.as_str() {
+                "direct" | "dns" | "block" | "selector" | "urltest" => return None,
+                "vless" | "vmess" | "trojan" | "shadowsocks" | "hysteria2" | "tuic" => {
+                    serde_json::from_value(outbound).map_err(Error::from)
+                }
+                _ => Err(Error::UnsupportedProtocol(type_name)),
+            };
+            Some((name, outbound))
+        })
+        .collect())
+}
+
+fn sip008_field(server: &Map<String, Value>, key: &str) -> Option<String> {
+    match server.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The SIP002 link a SIP008 server entry corresponds to
+fn sip008_link(server: &Map<String, Value>) -> Result<ShareLink, Error> {
+    let host: Host = sip008_field(server, "server")
+        .ok_or_else(|| Error::invalid_link("server", "SIP008 servers must have a server"))?
+        .parse()?;
+    let port = sip008_field(server, "server_port")
+        .and_then(|port| port.parse::<u16>().ok())
+        .ok_or_else(|| Error::invalid_link("server_port", "SIP008 servers must have a valid server_port"))?;
+    let method = sip008_field(server, "method")
+        .ok_or_else(|| Error::invalid_link("method", "SIP008 servers must have a method"))?;
+    let password = sip008_field(server, "password").unwrap_or_default();
+
+    let mut query = Vec::new();
+    if let Some(plugin) = sip008_field(server, "plugin") {
+        let plugin = match sip008_field(server, "plugin_opts") {
+            Some(opts) => format!("{};{}", plugin, opts),
+            None => plugin,
+        };
+        query.push(("plugin".to_string(), plugin));
+    }
+
+    Ok(ShareLink {
+        scheme: "ss".to_string(),
+        user_info: Some(format!("{}:{}", method, password)),
+        host,
+        port: Some(port),
+        path: String::new(),
+        query,
+        fragment: sip008_field(server, "remarks"),
+    })
+}
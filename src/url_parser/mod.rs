@@ -1,91 +1,550 @@
+#[allow(clippy::module_inception)]
 pub mod url_parser;
 
-use regex::Regex;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use serde_json::{Map, Value};
 use std::error::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
-use serde_json::{Value, Map};
 
+use crate::error::Error as CrateError;
 
-#[derive(Debug, Clone)]
-struct ParseError(String);
 
-impl ParseError {
-    fn new(msg: &str) -> Self {
-        ParseError(msg.to_string())
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    MissingScheme,
+    InvalidScheme(String),
+    MissingAuthority,
+    MissingHost,
+    InvalidHost(String),
+    InvalidPort(String),
+    InvalidPercentEncoding(String),
+    InvalidUtf8(String),
+    InvalidBase64,
+    InvalidVmessJson(String),
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingScheme => write!(f, "missing scheme"),
+            ParseError::InvalidScheme(s) => write!(f, "invalid scheme: {:?}", s),
+            ParseError::MissingAuthority => write!(f, "missing \"//\" authority"),
+            ParseError::MissingHost => write!(f, "missing host"),
+            ParseError::InvalidHost(s) => write!(f, "invalid host: {:?}", s),
+            ParseError::InvalidPort(s) => write!(f, "invalid port: {:?}", s),
+            ParseError::InvalidPercentEncoding(s) => write!(f, "invalid percent-encoding in {:?}", s),
+            ParseError::InvalidUtf8(s) => write!(f, "percent-decoded value is not UTF-8: {:?}", s),
+            ParseError::InvalidBase64 => write!(f, "invalid base64"),
+            ParseError::InvalidVmessJson(s) => write!(f, "invalid vmess JSON: {}", s),
+        }
     }
 }
 
 impl Error for ParseError {}
 
-impl From<regex::Error> for ParseError {
-    fn from(e: regex::Error) -> Self {
-        ParseError::new(&format!("Regex error: {}", e))
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Host {
+    Domain(String),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+}
+
+impl fmt::Display for Host {
+    /// Formats the host the way sing-box expects it in `server`, i.e. IPv6 without brackets
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Host::Domain(d) => write!(f, "{}", d),
+            Host::Ipv4(ip) => write!(f, "{}", ip),
+            Host::Ipv6(ip) => write!(f, "{}", ip),
+        }
+    }
+}
+
+impl FromStr for Host {
+    type Err = CrateError;
+
+    /// Parses a bare server address as found in JSON and YAML configs, where an
+    /// IPv6 address may appear with or without brackets
+    fn from_str(s: &str) -> Result<Host, CrateError> {
+        parse_server(s).map_err(|kind| CrateError::Parse { input: s.to_string(), kind })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareLink {
+    /// Lowercased scheme, e.g. `vless`
+    pub scheme: String,
+    /// Percent-decoded user-info (uuid, password, ...)
+    pub user_info: Option<String>,
+    pub host: Host,
+    pub port: Option<u16>,
+    /// Percent-decoded path, usually empty or `/`
+    pub path: String,
+    /// Percent-decoded query pairs in link order; a key may repeat
+    pub query: Vec<(String, String)>,
+    /// Percent-decoded `#name` fragment
+    pub fragment: Option<String>,
+}
+
+impl ShareLink {
+    /// First value of a query parameter
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of a query parameter, in link order
+    pub fn params(&self, key: &str) -> Vec<&str> {
+        self.query.iter().filter(|(k, _)| k == key).map(|(_, v)| v.as_str()).collect()
+    }
+}
+
+impl fmt::Display for ShareLink {
+    /// Formats the link as `scheme://user-info@host:port/path?query#fragment`, escaping
+    /// every component so that [`parse_url`] reads back the same link
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://", self.scheme)?;
+        if let Some(user_info) = &self.user_info {
+            // `:` separates method and password or uuid and password and is kept as is
+            let parts: Vec<String> = user_info.split(':').map(percent_encode).collect();
+            write!(f, "{}@", parts.join(":"))?;
+        }
+        match &self.host {
+            Host::Domain(domain) => write!(f, "{}", percent_encode(domain))?,
+            Host::Ipv4(ip) => write!(f, "{}", ip)?,
+            Host::Ipv6(ip) => write!(f, "[{}]", ip)?,
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        let segments: Vec<String> = self.path.split('/').map(percent_encode).collect();
+        write!(f, "{}", segments.join("/"))?;
+        for (i, (key, value)) in self.query.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, percent_encode(key), percent_encode(value))?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", percent_encode(fragment))?;
+        }
+        Ok(())
+    }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Escapes everything but RFC 3986 unreserved characters
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
     }
+    out
 }
 
-pub fn parse_url_params(params: String) -> Result<Map<String, Value>, Box<dyn Error>> {
-    let mut map = Map::new();
-    
-    for param in params.split("&") {
-        let parts: Vec<&str> = param.split("=").collect();
-        
-        if parts.len() != 2 {
-            return Err(Box::new(ParseError::new("Invalid parameter format")));
+/// Decodes `%XX` escapes. `+` is kept as is: share links carry base64 values
+/// where it is significant.
+pub fn percent_decode(s: &str) -> Result<String, CrateError> {
+    decode(s).map_err(|kind| CrateError::Parse { input: s.to_string(), kind })
+}
+
+fn decode(s: &str) -> Result<String, ParseError> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hi = bytes.get(i + 1).copied().and_then(hex_value);
+            let lo = bytes.get(i + 2).copied().and_then(hex_value);
+            match (hi, lo) {
+                (Some(hi), Some(lo)) => out.push(hi << 4 | lo),
+                _ => return Err(ParseError::InvalidPercentEncoding(s.to_string())),
+            }
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
         }
-        
-        let (key, value) = (parts[0], parts[1]);
-        map.insert(key.to_string(), Value::String(value.to_string()));
     }
-    
-    Ok(map)
+    String::from_utf8(out).map_err(|_| ParseError::InvalidUtf8(s.to_string()))
+}
+
+pub fn parse_url_params(params: &str) -> Result<Vec<(String, String)>, CrateError> {
+    parse_query(params).map_err(|kind| CrateError::Parse { input: redact_query(params), kind })
+}
+
+fn parse_query(params: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let mut query = Vec::new();
+
+    for param in params.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let key = decode(key)?;
+        let value = decode(value).map_err(|kind| if is_secret_param(&key) { redact_kind(kind) } else { kind })?;
+        query.push((key, value));
+    }
+
+    Ok(query)
+}
+
+fn is_valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
 }
 
-pub fn parse_url(url: &String) -> Result<Map<String, Value>, Box<dyn Error>> {
-    let mut dict = Map::new();
-    let url_regex = Regex::new(r"([a-z+]+)://([a-z0-9\-]+)@([a-z0-9]+(?:\.[a-z0-9]+)*):(\d+)(?:\?(.*))?")
-    .expect("Invalid regex pattern");
-    let captures = url_regex.captures(url).expect("Failed to parse URL");
-    
-    if captures.len() != 6 {
-        return Err(ParseError::new(format!("Wrong number of captures, expected 5: {:?}", captures).as_str()).into());
+fn parse_host(host: &str) -> Result<Host, ParseError> {
+    if host.is_empty() {
+        return Err(ParseError::MissingHost);
     }
 
-    let protocol: String = captures.get(1)
-        .map(|m| m.as_str())
-        .ok_or(ParseError::new("Failed to parse protocol"))?
-        .to_string();
-    let uuid: String = captures.get(2)
-        .map(|m| m.as_str())
-        .ok_or(ParseError::new("Failed to parse uuid"))?
-        .to_string();
-    let host : String = captures.get(3)
-        .map(|m| m.as_str())
-        .ok_or(ParseError::new("Failed to parse host"))?
-        .to_string();
-    let port : String = captures.get(4)
-        .map(|m| m.as_str())
-        .ok_or(ParseError::new("Failed to parse host"))?
-        .to_string();
-    let params: String = captures.get(5)
-        .map(|m| m.as_str())
-        .ok_or(ParseError::new("Failed to parse URL params"))?
-        .to_string();
+    if let Some(literal) = host.strip_prefix('[') {
+        let literal = literal.strip_suffix(']').ok_or_else(|| ParseError::InvalidHost(host.to_string()))?;
+        // Zone identifiers (`fe80::1%25eth0`) are not meaningful for a remote server
+        return literal.parse::<Ipv6Addr>()
+            .map(Host::Ipv6)
+            .map_err(|_| ParseError::InvalidHost(host.to_string()));
+    }
+
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return Ok(Host::Ipv4(ip));
+    }
+
+    // reg-name = *( unreserved / pct-encoded / sub-delims )
+    let valid_reg_name = host.chars().all(|c| {
+        c.is_ascii_alphanumeric() || "-._~%!$&'()*+,;=".contains(c)
+    });
+    if !valid_reg_name {
+        return Err(ParseError::InvalidHost(host.to_string()));
+    }
+
+    let domain = decode(host)?.to_lowercase();
+    if domain.is_empty() || domain.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        return Err(ParseError::InvalidHost(host.to_string()));
+    }
+    Ok(Host::Domain(domain))
+}
 
-    
-    dict.insert("protocol".to_string(), Value::String(protocol));
-    dict.insert("uuid".to_string(), Value::String(uuid));
-    dict.insert("host".to_string(), Value::String(host));
-    dict.insert("port".to_string(), Value::Number(serde_json::Number::from_str(port.as_str()).unwrap()));
-    dict.insert("params".to_string(), Value::Object(parse_url_params(params.to_string()).unwrap()));
+fn parse_server(server: &str) -> Result<Host, ParseError> {
+    match server.parse::<Ipv6Addr>() {
+        Ok(ip) => Ok(Host::Ipv6(ip)),
+        Err(_) => parse_host(server),
+    }
+}
 
-    Ok(dict)
+fn parse_port(port: &str) -> Result<Option<u16>, ParseError> {
+    if port.is_empty() {
+        return Ok(None);
+    }
+    if !port.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::InvalidPort(port.to_string()));
+    }
+    port.parse::<u16>()
+        .map(Some)
+        .map_err(|_| ParseError::InvalidPort(port.to_string()))
 }
 
+/// What error messages show in place of credentials
+const REDACTED: &str = "***";
+
+/// Query parameters whose values are credentials, e.g. the hysteria2 `auth` and
+/// `obfs-password`
+const SECRET_PARAMS: &[&str] = &["auth", "password", "obfs-password"];
 
+fn is_secret_param(key: &str) -> bool {
+    SECRET_PARAMS.iter().any(|secret| secret.eq_ignore_ascii_case(key))
+}
 
+/// The same error without the user-info or credential value it quotes
+fn redact_kind(kind: ParseError) -> ParseError {
+    match kind {
+        ParseError::InvalidPercentEncoding(_) => ParseError::InvalidPercentEncoding(REDACTED.to_string()),
+        ParseError::InvalidUtf8(_) => ParseError::InvalidUtf8(REDACTED.to_string()),
+        kind => kind,
+    }
+}
+
+/// `query` with the values of [`SECRET_PARAMS`] replaced by `***`
+fn redact_query(query: &str) -> String {
+    query.split('&')
+        .map(|param| match param.split_once('=') {
+            Some((key, _)) if is_secret_param(key) => format!("{}={}", key, REDACTED),
+            _ => param.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `input` with the user-info of a share link and the values of credential query
+/// parameters replaced by `***`, and the whole body of `vmess://` and legacy `ss://`
+/// links that base64-encode their credentials. Anything that isn't a link is
+/// returned as is.
+pub fn redact_link(input: &str) -> String {
+    let Some((scheme, body)) = input.split_once("://") else {
+        return input.to_string();
+    };
+    let (body, fragment) = match body.find('#') {
+        Some(idx) => body.split_at(idx),
+        None => (body, ""),
+    };
+    let (authority, query) = match body.split_once('?') {
+        Some((authority, query)) => (authority, Some(redact_query(query))),
+        None => (body, None),
+    };
+    let authority = match authority.rfind('@') {
+        Some(at) => format!("{}{}", REDACTED, &authority[at..]),
+        None if scheme.eq_ignore_ascii_case("vmess") || scheme.eq_ignore_ascii_case("ss") => REDACTED.to_string(),
+        None => authority.to_string(),
+    };
+    let query = query.map(|query| format!("?{}", query)).unwrap_or_default();
+    format!("{}://{}{}{}", scheme, authority, query, fragment)
+}
+
+/// Parses a share link of the form
+/// `scheme://[user-info@]host[:port][/path][?query][#fragment]` (RFC 3986).
+pub fn parse_url(url: &str) -> Result<ShareLink, CrateError> {
+    let url = url.trim();
+    parse_link(url).map_err(|kind| CrateError::Parse { input: url.to_string(), kind })
+}
+
+fn parse_link(url: &str) -> Result<ShareLink, ParseError> {
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(decode(fragment)?)),
+        None => (url, None),
+    };
+
+    let (scheme, rest) = rest.split_once(':').ok_or(ParseError::MissingScheme)?;
+    if scheme.is_empty() {
+        return Err(ParseError::MissingScheme);
+    }
+    if !is_valid_scheme(scheme) {
+        return Err(ParseError::InvalidScheme(scheme.to_string()));
+    }
+    let scheme = scheme.to_ascii_lowercase();
+
+    let rest = rest.strip_prefix("//").ok_or(ParseError::MissingAuthority)?;
+
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, parse_query(query)?),
+        None => (rest, Vec::new()),
+    };
+
+    // SIP002 user-info is standard base64 or a 2022 key, either of which may hold
+    // an unescaped '/', so an ss path only starts after the host
+    let host_start = match scheme.as_str() {
+        "ss" => rest.rfind('@').map_or(0, |at| at + 1),
+        _ => 0,
+    };
+    let (authority, path) = match rest[host_start..].find('/') {
+        Some(idx) => (&rest[..host_start + idx], decode(&rest[host_start + idx..])?),
+        None => (rest, String::new()),
+    };
+
+    // The last '@' separates user-info, which may itself contain an unescaped '@'
+    let (user_info, host_port) = match authority.rsplit_once('@') {
+        Some((user_info, host_port)) => (Some(decode(user_info).map_err(redact_kind)?), host_port),
+        None => (None, authority),
+    };
+
+    let (host, port) = if host_port.starts_with('[') {
+        match host_port.find(']') {
+            Some(end) => {
+                let after = &host_port[end + 1..];
+                let port = match after.strip_prefix(':') {
+                    Some(port) => port,
+                    None if after.is_empty() => "",
+                    None => return Err(ParseError::InvalidHost(host_port.to_string())),
+                };
+                (&host_port[..=end], port)
+            }
+            None => return Err(ParseError::InvalidHost(host_port.to_string())),
+        }
+    } else {
+        host_port.rsplit_once(':').unwrap_or((host_port, ""))
+    };
+
+    Ok(ShareLink {
+        scheme,
+        user_info,
+        host: parse_host(host)?,
+        port: parse_port(port)?,
+        path,
+        query,
+        fragment,
+    })
+}
+
+/// Decodes standard or URL-safe base64, padded or not, ignoring whitespace
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD].iter()
+        .find_map(|engine| engine.decode(&s).ok())
+}
+
+/// Field of a v2rayN vmess object; panels write numbers both as strings and as numbers
+fn vmess_field(json: &Map<String, Value>, key: &str) -> Option<String> {
+    match json.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Translates a v2rayN `vmess://base64(json)` link into the query parameters
+/// the other share links use, so the transport and TLS mapping can be shared
+fn parse_vmess_json(body: &str) -> Result<ShareLink, ParseError> {
+    let decoded = decode_base64(body).ok_or(ParseError::InvalidBase64)?;
+    let json: Map<String, Value> = serde_json::from_slice(&decoded)
+        .map_err(|e| ParseError::InvalidVmessJson(e.to_string()))?;
+
+    let add = vmess_field(&json, "add").ok_or(ParseError::MissingHost)?;
+    let host = parse_server(&add)?;
+    let port = match vmess_field(&json, "port") {
+        Some(port) => parse_port(&port)?,
+        None => None,
+    };
+
+    let net = vmess_field(&json, "net").unwrap_or_else(|| "tcp".to_string());
+    let mut query = vec![("type".to_string(), net.clone())];
+    let mut push = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            query.push((key.to_string(), value));
+        }
+    };
+
+    push("encryption", vmess_field(&json, "scy"));
+    push("aid", vmess_field(&json, "aid"));
+    push("security", vmess_field(&json, "tls"));
+    push("sni", vmess_field(&json, "sni"));
+    push("alpn", vmess_field(&json, "alp").or_else(|| vmess_field(&json, "alpn")));
+    push("fp", vmess_field(&json, "fp"));
+    push("allowInsecure", vmess_field(&json, "allowInsecure").or_else(|| vmess_field(&json, "insecure")));
+    push("host", vmess_field(&json, "host"));
+    match net.as_str() {
+        // v2rayN stores the gRPC service name in `path` and the gRPC/XHTTP mode in `type`
+        "grpc" => {
+            push("serviceName", vmess_field(&json, "path"));
+            push("mode", vmess_field(&json, "type"));
+        }
+        "xhttp" | "splithttp" => {
+            push("path", vmess_field(&json, "path"));
+            push("mode", vmess_field(&json, "type"));
+        }
+        _ => {
+            push("path", vmess_field(&json, "path"));
+            push("headerType", vmess_field(&json, "type"));
+        }
+    }
+
+    Ok(ShareLink {
+        scheme: "vmess".to_string(),
+        user_info: vmess_field(&json, "id"),
+        host,
+        port,
+        path: String::new(),
+        query,
+        fragment: vmess_field(&json, "ps"),
+    })
+}
+
+/// Formats a `vmess://` link as the v2rayN `vmess://base64(json)` most clients expect,
+/// the inverse of what [`parse_share_link`] does with such links
+pub fn format_vmess_link(link: &ShareLink) -> String {
+    let mut json = Map::new();
+    let mut put = |key: &str, value: Option<&str>| {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            json.insert(key.to_string(), Value::String(value.to_string()));
+        }
+    };
+
+    let net = link.param("type").unwrap_or("tcp");
+    put("v", Some("2"));
+    put("ps", link.fragment.as_deref());
+    put("add", Some(&link.host.to_string()));
+    put("port", link.port.map(|port| port.to_string()).as_deref());
+    put("id", link.user_info.as_deref());
+    put("aid", link.param("aid"));
+    put("scy", link.param("encryption"));
+    put("net", Some(net));
+    put("host", link.param("host"));
+    match net {
+        "grpc" => {
+            put("path", link.param("serviceName"));
+            put("type", link.param("mode"));
+        }
+        "xhttp" | "splithttp" => {
+            put("path", link.param("path"));
+            put("type", link.param("mode"));
+        }
+        // v2rayN has no field for ws early data; Xray reads it from the path
+        "ws" if link.param("ed").is_some() => {
+            let path = format!("{}?ed={}", link.param("path").unwrap_or("/"), link.param("ed").unwrap_or_default());
+            put("path", Some(&path));
+        }
+        _ => {
+            put("path", link.param("path"));
+            put("type", link.param("headerType"));
+        }
+    }
+    put("tls", link.param("security").filter(|security| *security != "none"));
+    put("sni", link.param("sni"));
+    put("alpn", link.param("alpn"));
+    put("fp", link.param("fp"));
+    put("allowInsecure", link.param("allowInsecure"));
+
+    format!("vmess://{}", STANDARD.encode(Value::Object(json).to_string()))
+}
+
+/// `sing-box://import-remote-profile` link the sing-box apps open to subscribe to the
+/// profile served at `url`
+pub fn format_remote_profile_link(url: &str, name: Option<&str>) -> String {
+    ShareLink {
+        scheme: "sing-box".to_string(),
+        user_info: None,
+        host: Host::Domain("import-remote-profile".to_string()),
+        port: None,
+        path: String::new(),
+        query: vec![("url".to_string(), url.to_string())],
+        fragment: name.map(str::to_string),
+    }.to_string()
+}
+
+/// Rewrites a pre-SIP002 `ss://base64(method:password@host:port)#tag` link
+/// into the SIP002 shape
+fn expand_legacy_ss(body: &str) -> Result<String, ParseError> {
+    let end = body.find(['?', '#']).unwrap_or(body.len());
+    let (encoded, rest) = body.split_at(end);
+    let decoded = decode_base64(encoded).ok_or(ParseError::InvalidBase64)?;
+    let decoded = String::from_utf8(decoded).map_err(|_| ParseError::InvalidUtf8(REDACTED.to_string()))?;
+    let (user_info, host_port) = decoded.rsplit_once('@').ok_or(ParseError::MissingHost)?;
+    Ok(format!("ss://{}@{}{}", percent_encode(user_info), host_port, rest))
+}
+
+/// Parses any supported share link: v2rayN style `vmess://base64(json)` links
+/// are decoded into a [`ShareLink`], legacy all-base64 `ss://` links are expanded,
+/// everything else goes through [`parse_url`]
+pub fn parse_share_link(url: &str) -> Result<ShareLink, CrateError> {
+    let url = url.trim();
+    if let Some((scheme, body)) = url.split_once("://") {
+        let authority = body.split(['?', '#']).next().unwrap_or_default();
+        if scheme.eq_ignore_ascii_case("vmess") && !body.contains('@') {
+            return parse_vmess_json(body).map_err(|kind| CrateError::Parse { input: url.to_string(), kind });
+        }
+        if scheme.eq_ignore_ascii_case("ss") && !authority.contains('@') {
+            let expanded = expand_legacy_ss(body).map_err(|kind| CrateError::Parse { input: url.to_string(), kind })?;
+            return parse_url(&expanded);
+        }
+    }
+    parse_url(url)
+}
@@ -0,0 +1,498 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::Error;
+use crate::models::{DnsServer, Outbound, SingBoxConfig, TlsConfig, Unmapped, V2RayTransport};
+
+
+
+/// Ports of the inbounds every rendered Xray config listens on
+pub const XRAY_SOCKS_PORT: u16 = 10808;
+pub const XRAY_HTTP_PORT: u16 = 10809;
+pub const XRAY_DNS_PORT: u16 = 10853;
+
+/// Tag of the inbound whose queries go to Xray's DNS module
+const XRAY_DNS_INBOUND: &str = "dns-in";
+/// Tag of the dns outbound added when the config has none
+const XRAY_DNS_TAG: &str = "dns-out";
+/// Tag of the blackhole outbound `reject` rules are sent to
+const XRAY_BLOCK_TAG: &str = "block";
+
+/// An Xray-core `config.json` rendered from a sing-box config
+#[derive(Debug)]
+pub struct XrayExport {
+    pub config: Value,
+    pub unmapped: Vec<Unmapped>,
+}
+
+impl TlsConfig {
+    /// `security` and its settings in an Xray `streamSettings`
+    fn xray_security(&self, stream: &mut Map<String, Value>) {
+        if !self.enabled {
+            stream.insert("security".to_string(), json!("none"));
+            return;
+        }
+        let mut settings = Map::new();
+        if let Some(server_name) = &self.server_name {
+            settings.insert("serverName".to_string(), json!(server_name));
+        }
+        let fingerprint = self.utls.as_ref().map(|utls| utls.fingerprint.as_str());
+        match &self.reality {
+            Some(reality) => {
+                // Xray refuses REALITY without a fingerprint
+                settings.insert("fingerprint".to_string(), json!(fingerprint.unwrap_or("chrome")));
+                settings.insert("publicKey".to_string(), json!(reality.public_key.as_deref().unwrap_or_default()));
+                settings.insert("shortId".to_string(), json!(reality.short_id));
+                stream.insert("security".to_string(), json!("reality"));
+                stream.insert("realitySettings".to_string(), Value::Object(settings));
+            }
+            None => {
+                if let Some(fingerprint) = fingerprint {
+                    settings.insert("fingerprint".to_string(), json!(fingerprint));
+                }
+                if let Some(alpn) = &self.alpn {
+                    settings.insert("alpn".to_string(), json!(alpn));
+                }
+                if self.insecure == Some(true) {
+                    settings.insert("allowInsecure".to_string(), json!(true));
+                }
+                stream.insert("security".to_string(), json!("tls"));
+                stream.insert("tlsSettings".to_string(), Value::Object(settings));
+            }
+        }
+    }
+}
+
+impl V2RayTransport {
+    /// Xray `streamSettings` keys for this transport: the `network` and its settings.
+    /// sing-box's http transport is HTTP/2 over tls, which Xray no longer has, and
+    /// HTTP/1.1 header obfuscation of tcp without it.
+    fn xray_stream(&self, tls: bool) -> Result<Map<String, Value>, Error> {
+        let (network, key, settings) = match self {
+            V2RayTransport::Http(_) if tls => return Err(Error::UnsupportedTransport("http over tls".to_string())),
+            V2RayTransport::Http(http) => {
+                let mut request = json!({ "path": [http.path.as_deref().unwrap_or("/")] });
+                if let Some(host) = &http.host {
+                    request["headers"] = json!({ "Host": host });
+                }
+                ("tcp", "tcpSettings", json!({ "header": { "type": "http", "request": request } }))
+            }
+            V2RayTransport::Ws(ws) => {
+                // Xray reads the early data size from the path
+                let mut path = ws.path.clone().unwrap_or_else(|| "/".to_string());
+                if let Some(ed) = ws.max_early_data {
+                    path = format!("{}?ed={}", path, ed);
+                }
+                let mut settings = json!({ "path": path });
+                if let Some(headers) = &ws.headers {
+                    settings["headers"] = Value::Object(headers.clone());
+                }
+                ("ws", "wsSettings", settings)
+            }
+            V2RayTransport::Grpc(grpc) => ("grpc", "grpcSettings", json!({ "serviceName": grpc.service_name.as_deref().unwrap_or_default() })),
+            V2RayTransport::HttpUpgrade(upgrade) => ("httpupgrade", "httpupgradeSettings", json!({
+                "host": upgrade.host.as_deref().unwrap_or_default(),
+                "path": upgrade.path.as_deref().unwrap_or("/"),
+            })),
+            V2RayTransport::Quic(_) => return Err(Error::UnsupportedTransport("quic".to_string())),
+            V2RayTransport::Xhttp(xhttp) => {
+                let mut settings = json!({
+                    "host": xhttp.host.as_deref().unwrap_or_default(),
+                    "path": xhttp.path.as_deref().unwrap_or("/"),
+                });
+                if let Some(mode) = &xhttp.mode {
+                    settings["mode"] = json!(mode);
+                }
+                ("xhttp", "xhttpSettings", settings)
+            }
+        };
+        let mut stream = Map::new();
+        stream.insert("network".to_string(), json!(network));
+        stream.insert(key.to_string(), settings);
+        Ok(stream)
+    }
+}
+
+/// Xray `streamSettings` of a V2Ray based outbound; no transport is raw tcp
+fn xray_stream(tls: &Option<TlsConfig>, transport: &Option<V2RayTransport>) -> Result<Value, Error> {
+    let tls = tls.as_ref().filter(|tls| tls.enabled);
+    let mut stream = match transport {
+        Some(transport) => transport.xray_stream(tls.is_some())?,
+        None => {
+            let mut stream = Map::new();
+            stream.insert("network".to_string(), json!("tcp"));
+            stream
+        }
+    };
+    match tls {
+        Some(tls) => tls.xray_security(&mut stream),
+        None => {
+            stream.insert("security".to_string(), json!("none"));
+        }
+    }
+    Ok(Value::Object(stream))
+}
+
+/// sing-box log levels in Xray's terms
+fn xray_log_level(level: &str) -> &'static str {
+    match level {
+        "trace" | "debug" => "debug",
+        "info" => "info",
+        "warn" => "warning",
+        _ => "error",
+    }
+}
+
+impl Outbound {
+    /// The Xray outbound object for this outbound. Xray has no Hysteria 2, TUIC or
+    /// SIP003 plugins; selector and urltest groups are rendered by
+    /// [`SingBoxConfig::to_xray`] as routing instead.
+    pub fn to_xray(&self) -> Result<Value, Error> {
+        let (protocol, settings, stream) = match self {
+            Outbound::Direct(_) => return Ok(json!({ "tag": self.tag(), "protocol": "freedom" })),
+            Outbound::Dns(_) => return Ok(json!({ "tag": self.tag(), "protocol": "dns" })),
+            Outbound::Vless(out) => {
+                let mut user = json!({ "id": out.uuid, "encryption": "none" });
+                if let Some(flow) = &out.flow {
+                    user["flow"] = json!(flow);
+                }
+                let settings = json!({ "vnext": [{ "address": out.server, "port": out.server_port, "users": [user] }] });
+                ("vless", settings, Some(xray_stream(&out.tls, &out.transport)?))
+            }
+            Outbound::Vmess(out) => {
+                let user = json!({
+                    "id": out.uuid,
+                    "alterId": out.alter_id.unwrap_or(0),
+                    "security": out.security.as_deref().unwrap_or("auto"),
+                });
+                let settings = json!({ "vnext": [{ "address": out.server, "port": out.server_port, "users": [user] }] });
+                ("vmess", settings, Some(xray_stream(&out.tls, &out.transport)?))
+            }
+            Outbound::Trojan(out) => {
+                let settings = json!({ "servers": [{ "address": out.server, "port": out.server_port, "password": out.password }] });
+                ("trojan", settings, Some(xray_stream(&out.tls, &out.transport)?))
+            }
+            Outbound::Shadowsocks(out) => {
+                if let Some(plugin) = &out.plugin {
+                    return Err(Error::UnsupportedProtocol(format!("shadowsocks with {}", plugin)));
+                }
+                let settings = json!({ "servers": [{
+                    "address": out.server,
+                    "port": out.server_port,
+                    "method": out.method,
+                    "password": out.password,
+                }] });
+                ("shadowsocks", settings, None)
+            }
+            Outbound::Hysteria2(_) | Outbound::Tuic(_) | Outbound::Selector(_) | Outbound::UrlTest(_) => {
+                return Err(Error::UnsupportedProtocol(self.type_name().to_string()));
+            }
+        };
+
+        let mut outbound = json!({ "tag": self.tag(), "protocol": protocol, "settings": settings });
+        if let Some(stream) = stream {
+            outbound["streamSettings"] = stream;
+        }
+        Ok(outbound)
+    }
+}
+
+/// What a routing rule points at in an Xray config
+enum XrayTarget {
+    Outbound(String),
+    Balancer(String),
+}
+
+impl XrayTarget {
+    fn insert_into(self, rule: &mut Value) {
+        match self {
+            XrayTarget::Outbound(tag) => rule["outboundTag"] = json!(tag),
+            XrayTarget::Balancer(tag) => rule["balancerTag"] = json!(tag),
+        }
+    }
+}
+
+impl SingBoxConfig {
+    /// Renders the outbounds and `route` of this config as an Xray-core config. The
+    /// inbounds are replaced with a socks and an http proxy on localhost plus a
+    /// dokodemo-door whose queries Xray's DNS module answers from `dns.servers`.
+    ///
+    /// A urltest becomes a `leastPing` balancer; a selector has no Xray counterpart
+    /// and routes to its default member. Balancers pick their members by tag prefix,
+    /// so a member that another tag starts with gets a suffix in the Xray config.
+    /// Outbounds and rules Xray cannot express are left out and listed in
+    /// [`XrayExport::unmapped`].
+    pub fn to_xray(&self) -> XrayExport {
+        let mut unmapped = Vec::new();
+
+        let mut outbounds = Vec::new();
+        for (i, out) in self.outbounds().iter().enumerate() {
+            if matches!(out, Outbound::Selector(_) | Outbound::UrlTest(_)) {
+                continue;
+            }
+            match out.to_xray() {
+                Ok(outbound) => outbounds.push(outbound),
+                Err(reason) => unmapped.push(Unmapped { path: format!("outbounds[{}]", i), reason: reason.to_string() }),
+            }
+        }
+        let tags: Vec<String> = outbounds.iter()
+            .filter_map(|out| out["tag"].as_str().map(str::to_string))
+            .collect();
+        let members: Vec<&String> = self.outbounds().iter()
+            .filter_map(|out| match out {
+                Outbound::UrlTest(urltest) => Some(&urltest.outbounds),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let renamed = xray_tags(&tags, &members);
+        for (out, tag) in outbounds.iter_mut().zip(&renamed) {
+            out["tag"] = json!(tag);
+        }
+        // The sing-box tag of each rendered outbound with its tag in the Xray config
+        let rendered: Vec<(String, String)> = tags.into_iter().zip(renamed).collect();
+
+        // Balancers select outbounds by tag prefix, so every member is listed in full
+        let mut balancers = Vec::new();
+        let mut observatory: Option<Value> = None;
+        for (i, out) in self.outbounds().iter().enumerate() {
+            let Outbound::UrlTest(urltest) = out else {
+                continue;
+            };
+            let members: Vec<&String> = urltest.outbounds.iter()
+                .filter_map(|member| rendered.iter().find(|(tag, _)| tag == member).map(|(_, xray)| xray))
+                .collect();
+            if members.is_empty() {
+                unmapped.push(Unmapped { path: format!("outbounds[{}]", i), reason: "none of its outbounds are in the Xray config".to_string() });
+                continue;
+            }
+            balancers.push(json!({ "tag": urltest.tag, "selector": members, "strategy": { "type": "leastPing" } }));
+
+            // Xray probes with a single observatory
+            let observatory = observatory.get_or_insert_with(|| json!({ "subjectSelector": [] }));
+            for member in members {
+                if let Some(subjects) = observatory["subjectSelector"].as_array_mut()
+                    && !subjects.contains(&json!(member)) {
+                    subjects.push(json!(member));
+                }
+            }
+            if let Some(url) = &urltest.url && observatory.get("probeURL").is_none() {
+                observatory["probeURL"] = json!(url);
+            }
+            if let Some(interval) = &urltest.interval && observatory.get("probeInterval").is_none() {
+                observatory["probeInterval"] = json!(interval);
+            }
+        }
+        let balancer_tags: Vec<String> = balancers.iter()
+            .filter_map(|balancer| balancer["tag"].as_str().map(str::to_string))
+            .collect();
+        let target = |tag: &str| self.xray_target(tag, &rendered, &balancer_tags, &mut Vec::new());
+
+        let dns_outbound = match self.outbounds().iter().find(|out| matches!(out, Outbound::Dns(_))) {
+            Some(dns) => dns.tag().to_string(),
+            None => {
+                outbounds.push(json!({ "tag": XRAY_DNS_TAG, "protocol": "dns" }));
+                XRAY_DNS_TAG.to_string()
+            }
+        };
+        let mut rules = vec![json!({ "type": "field", "inboundTag": [XRAY_DNS_INBOUND], "outboundTag": dns_outbound })];
+        let mut block = false;
+        for (i, rule) in self.route_rules().iter().enumerate() {
+            let path = format!("route.rules[{}]", i);
+            // DNS reaches Xray through the dokodemo-door rather than by sniffing
+            if rule.protocol.as_deref() == Some("dns") || rule.action.as_deref() == Some("hijack-dns") {
+                continue;
+            }
+
+            let mut skip = |field: &str, reason: &str| unmapped.push(Unmapped { path: format!("{}.{}", path, field), reason: reason.to_string() });
+            let mut mappable = true;
+            if rule.inbound.is_some() {
+                skip("inbound", "the sing-box inbounds are not in the Xray config");
+                mappable = false;
+            }
+            if rule.process_name.is_some() {
+                skip("process_name", "Xray cannot match processes");
+                mappable = false;
+            }
+            if rule.ip_is_private == Some(false) {
+                skip("ip_is_private", "Xray cannot match only public addresses");
+                mappable = false;
+            }
+            for key in rule.extra.keys() {
+                skip(key, "no Xray equivalent");
+                mappable = false;
+            }
+
+            let mut xray = json!({ "type": "field" });
+            if let Some(protocol) = &rule.protocol {
+                xray["protocol"] = json!([protocol]);
+            }
+            let domains: Vec<String> = rule.domain_suffix.iter().flatten().map(|suffix| format!("domain:{}", suffix)).collect();
+            let mut ips = rule.ip_cidr.clone().unwrap_or_default();
+            if rule.ip_is_private == Some(true) {
+                ips.push("geoip:private".to_string());
+            }
+            if let Some(port) = rule.port {
+                xray["port"] = json!(port);
+            }
+
+            match (rule.action.as_deref(), &rule.outbound) {
+                (Some("reject"), _) => {
+                    block = true;
+                    xray["outboundTag"] = json!(XRAY_BLOCK_TAG);
+                }
+                (None | Some("route"), Some(outbound)) => match target(outbound) {
+                    Some(target) => target.insert_into(&mut xray),
+                    None => {
+                        skip("outbound", &format!("{:?} is not in the Xray config", outbound));
+                        mappable = false;
+                    }
+                },
+                (action, _) => {
+                    skip("action", &format!("unsupported action {:?}", action.unwrap_or_default()));
+                    mappable = false;
+                }
+            }
+            // Leaving out a condition would widen the rule, so the whole rule goes
+            if !mappable {
+                continue;
+            }
+            // sing-box matches the domains or the addresses of a rule, while Xray wants
+            // both of a rule's, so each gets a rule of its own
+            let destinations: Vec<(&str, Vec<String>)> = [("domain", domains), ("ip", ips)].into_iter()
+                .filter(|(_, values)| !values.is_empty())
+                .collect();
+            if destinations.is_empty() {
+                rules.push(xray);
+                continue;
+            }
+            for (key, values) in destinations {
+                let mut split = xray.clone();
+                split[key] = json!(values);
+                rules.push(split);
+            }
+        }
+        if block {
+            outbounds.push(json!({ "tag": XRAY_BLOCK_TAG, "protocol": "blackhole" }));
+        }
+
+        // Xray sends unmatched traffic to the first outbound
+        if let Some(final_tag) = self.route_final() {
+            match target(final_tag) {
+                Some(XrayTarget::Outbound(tag)) => {
+                    if let Some(i) = outbounds.iter().position(|out| out["tag"] == json!(tag)) {
+                        let first = outbounds.remove(i);
+                        outbounds.insert(0, first);
+                    }
+                }
+                Some(balancer) => {
+                    let mut rule = json!({ "type": "field", "network": "tcp,udp" });
+                    balancer.insert_into(&mut rule);
+                    rules.push(rule);
+                }
+                None => unmapped.push(Unmapped {
+                    path: "route.final".to_string(),
+                    reason: format!("{:?} is not in the Xray config", final_tag),
+                }),
+            }
+        }
+
+        // The final server is queried first
+        let mut servers: Vec<&DnsServer> = self.dns.servers.iter().collect();
+        servers.sort_by_key(|server| server.tag != self.dns.final_field);
+        let servers: Vec<&str> = servers.iter()
+            .map(|server| match server.address.as_str() {
+                "local" => "localhost",
+                address => address,
+            })
+            .collect();
+
+        let sniffing = json!({ "enabled": true, "destOverride": ["http", "tls", "quic"], "routeOnly": true });
+        let mut config = json!({
+            "log": { "loglevel": xray_log_level(&self.log.level) },
+            "dns": { "servers": servers },
+            "inbounds": [
+                {
+                    "tag": "socks-in",
+                    "protocol": "socks",
+                    "listen": "127.0.0.1",
+                    "port": XRAY_SOCKS_PORT,
+                    "settings": { "udp": true },
+                    "sniffing": sniffing,
+                },
+                {
+                    "tag": "http-in",
+                    "protocol": "http",
+                    "listen": "127.0.0.1",
+                    "port": XRAY_HTTP_PORT,
+                    "sniffing": sniffing,
+                },
+                {
+                    "tag": XRAY_DNS_INBOUND,
+                    "protocol": "dokodemo-door",
+                    "listen": "127.0.0.1",
+                    "port": XRAY_DNS_PORT,
+                    "settings": { "address": "1.1.1.1", "port": 53, "network": "tcp,udp" },
+                },
+            ],
+            "outbounds": outbounds,
+            "routing": { "domainStrategy": "AsIs", "rules": rules },
+        });
+        if !balancers.is_empty() {
+            config["routing"]["balancers"] = json!(balancers);
+        }
+        if let Some(observatory) = observatory {
+            config["observatory"] = observatory;
+        }
+
+        XrayExport { config, unmapped }
+    }
+
+    /// Where Xray sends what sing-box sends to `tag`: the outbound itself, the
+    /// balancer standing in for a urltest, or what a selector's default member maps to
+    fn xray_target(&self, tag: &str, rendered: &[(String, String)], balancers: &[String], seen: &mut Vec<String>) -> Option<XrayTarget> {
+        if let Some((_, xray)) = rendered.iter().find(|(rendered, _)| rendered == tag) {
+            return Some(XrayTarget::Outbound(xray.clone()));
+        }
+        if balancers.iter().any(|balancer| balancer == tag) {
+            return Some(XrayTarget::Balancer(tag.to_string()));
+        }
+        if seen.iter().any(|seen| seen == tag) {
+            return None;
+        }
+        seen.push(tag.to_string());
+        match self.outbounds().iter().find(|out| out.tag() == tag) {
+            Some(Outbound::Selector(selector)) => selector.default.iter()
+                .chain(&selector.outbounds)
+                .find_map(|member| self.xray_target(member, rendered, balancers, seen)),
+            _ => None,
+        }
+    }
+}
+
+/// Xray tags for outbounds tagged `tags` in sing-box. Balancers and the observatory
+/// pick outbounds by tag prefix, so a urltest member that another tag starts with
+/// gets a `-2`, `-3`, ... suffix no other tag shares a prefix with.
+fn xray_tags(tags: &[String], members: &[&String]) -> Vec<String> {
+    let mut xray = tags.to_vec();
+    for i in 0..xray.len() {
+        if !members.contains(&&tags[i]) {
+            continue;
+        }
+        let others: Vec<String> = xray.iter().enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, tag)| tag.clone())
+            .chain([XRAY_DNS_TAG, XRAY_BLOCK_TAG].map(str::to_string))
+            .collect();
+        if !others.iter().any(|other| other.starts_with(&xray[i])) {
+            continue;
+        }
+        let mut n = 2;
+        let mut candidate = format!("{}-{}", tags[i], n);
+        while others.iter().any(|other| other.starts_with(&candidate) || candidate.starts_with(other.as_str())) {
+            n += 1;
+            candidate = format!("{}-{}", tags[i], n);
+        }
+        xray[i] = candidate;
+    }
+    xray
+}
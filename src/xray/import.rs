@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use serde_json::{json, Map, Value};
+
+use super::{outbound_to_link, Fields, XrayImport, XrayProfile};
+use crate::models::{unique_tag, Outbound, RouteRule, SingBoxConfig, Unmapped, UrlTestOutbound};
+
+
+/// What an Xray outbound tag stands for in an imported config
+enum ImportedTarget {
+    Outbound(String),
+    /// A `blackhole`, which sing-box expresses as the `reject` action
+    Reject,
+    /// Xray's `dns` outbound, whose job the template's DNS rules do
+    Dns,
+}
+
+/// An address or CIDR of an Xray `ip` or `source` list as a CIDR
+fn xray_cidr(ip: &str) -> Option<String> {
+    if let Some((address, prefix)) = ip.split_once('/') {
+        let max = if address.parse::<IpAddr>().ok()?.is_ipv4() { 32 } else { 128 };
+        if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        return prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max).map(|_| ip.to_string());
+    }
+    match ip.parse::<IpAddr>().ok()? {
+        IpAddr::V4(address) => Some(format!("{}/32", address)),
+        IpAddr::V6(address) => Some(format!("{}/128", address)),
+    }
+}
+
+/// Translates an Xray routing rule. Values of a field are alternatives and fields
+/// must all match, as in sing-box, except that sing-box also takes domains and IPs
+/// as alternatives. Conditions that cannot be carried over are reported and the
+/// whole rule is left out, since dropping a condition would widen it.
+fn route_rule_from_xray(
+    rule: &Map<String, Value>,
+    path: &str,
+    targets: &HashMap<String, ImportedTarget>,
+    balancers: &HashMap<String, String>,
+    unmapped: &mut Vec<Unmapped>,
+) -> Option<RouteRule> {
+    let mut fields = Fields::new(path, rule.clone());
+    let mut problems = Vec::new();
+    let mut route_rule = RouteRule::default();
+    fields.take("type");
+    fields.take("ruleTag");
+
+    match (fields.string("outboundTag"), fields.string("balancerTag")) {
+        (Some(tag), _) => match targets.get(&tag) {
+            Some(ImportedTarget::Outbound(outbound)) => route_rule.outbound = Some(outbound.clone()),
+            Some(ImportedTarget::Reject) => route_rule.action = Some("reject".to_string()),
+            Some(ImportedTarget::Dns) => return None,
+            None => problems.push(Unmapped { path: fields.child_path("outboundTag"), reason: format!("{:?} was not imported", tag) }),
+        },
+        (None, Some(tag)) => match balancers.get(&tag) {
+            Some(outbound) => route_rule.outbound = Some(outbound.clone()),
+            None => problems.push(Unmapped { path: fields.child_path("balancerTag"), reason: format!("{:?} was not imported", tag) }),
+        },
+        (None, None) => problems.push(Unmapped { path: path.to_string(), reason: "the rule has no outboundTag or balancerTag".to_string() }),
+    }
+
+    let mut domains: [Vec<String>; 4] = Default::default();
+    for domain in fields.strings("domain") {
+        let (kind, value) = match domain.split_once(':') {
+            Some(("domain", value)) => (0, value),
+            Some(("full", value)) => (1, value),
+            Some(("keyword", value)) => (2, value),
+            Some(("regexp", value)) => (3, value),
+            Some(("geosite" | "ext", _)) => {
+                problems.push(Unmapped { path: fields.child_path("domain"), reason: format!("{} needs a sing-box rule set", domain) });
+                continue;
+            }
+            // Xray matches a plain string anywhere in the domain
+            _ => (2, domain.as_str()),
+        };
+        domains[kind].push(value.to_string());
+    }
+    let [suffixes, full, keywords, regexes] = domains;
+    let has_domain = !(suffixes.is_empty() && full.is_empty() && keywords.is_empty() && regexes.is_empty());
+    if !suffixes.is_empty() {
+        route_rule.domain_suffix = Some(suffixes);
+    }
+    for (key, values) in [("domain", full), ("domain_keyword", keywords), ("domain_regex", regexes)] {
+        if !values.is_empty() {
+            route_rule.extra.insert(key.to_string(), json!(values));
+        }
+    }
+
+    let mut cidrs = Vec::new();
+    for ip in fields.strings("ip") {
+        if ip == "geoip:private" {
+            route_rule.ip_is_private = Some(true);
+        } else if let Some(cidr) = xray_cidr(&ip) {
+            cidrs.push(cidr);
+        } else {
+            let reason = if ip.starts_with("geoip:") || ip.starts_with("ext:") {
+                format!("{} needs a sing-box rule set", ip)
+            } else {
+                format!("{:?} is not an IP address or CIDR", ip)
+            };
+            problems.push(Unmapped { path: fields.child_path("ip"), reason });
+        }
+    }
+    let has_ip = !cidrs.is_empty() || route_rule.ip_is_private.is_some();
+    if !cidrs.is_empty() {
+        route_rule.ip_cidr = Some(cidrs);
+    }
+    if has_domain && has_ip {
+        problems.push(Unmapped {
+            path: path.to_string(),
+            reason: "Xray needs both the domain and the ip to match, sing-box either".to_string(),
+        });
+    }
+
+    let mut ranges = Vec::new();
+    for port in fields.strings("port") {
+        let (start, end) = port.split_once('-').unwrap_or((&port, &port));
+        match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+            (Ok(start), Ok(end)) => ranges.push((start, end)),
+            _ => problems.push(Unmapped { path: fields.child_path("port"), reason: format!("{:?} is not a port or range", port) }),
+        }
+    }
+    match ranges.as_slice() {
+        [] => {}
+        [(start, end)] if start == end => route_rule.port = Some(i32::from(*start)),
+        ranges => {
+            let ranges: Vec<String> = ranges.iter().map(|(start, end)| format!("{}:{}", start, end)).collect();
+            route_rule.extra.insert("port_range".to_string(), json!(ranges));
+        }
+    }
+
+    let networks = fields.strings("network");
+    match networks.as_slice() {
+        [network] if network == "tcp" || network == "udp" => {
+            route_rule.extra.insert("network".to_string(), json!(network));
+        }
+        networks if networks.iter().all(|network| network == "tcp" || network == "udp") => {}
+        _ => problems.push(Unmapped { path: fields.child_path("network"), reason: format!("unknown network {:?}", networks.join(",")) }),
+    }
+
+    match fields.strings("protocol").as_slice() {
+        [] => {}
+        [protocol] => route_rule.protocol = Some(protocol.clone()),
+        _ => problems.push(Unmapped { path: fields.child_path("protocol"), reason: "a route rule matches a single protocol".to_string() }),
+    }
+
+    let mut sources = Vec::new();
+    for key in ["source", "sourceIP"] {
+        for source in fields.strings(key) {
+            match xray_cidr(&source) {
+                Some(cidr) => sources.push(cidr),
+                None => problems.push(Unmapped { path: fields.child_path(key), reason: format!("{:?} is not an IP address or CIDR", source) }),
+            }
+        }
+    }
+    if !sources.is_empty() {
+        route_rule.extra.insert("source_ip_cidr".to_string(), json!(sources));
+    }
+
+    if fields.take("inboundTag").is_some() {
+        problems.push(Unmapped { path: fields.child_path("inboundTag"), reason: "the Xray inbounds are not imported".to_string() });
+    }
+    fields.finish(&mut problems);
+
+    if problems.is_empty() {
+        Some(route_rule)
+    } else {
+        unmapped.extend(problems);
+        None
+    }
+}
+
+impl RouteRule {
+    /// A rule without conditions, i.e. one that matches all traffic
+    fn matches_all(&self) -> bool {
+        self.inbound.is_none()
+            && self.protocol.is_none()
+            && self.ip_cidr.is_none()
+            && self.domain_suffix.is_none()
+            && self.ip_is_private.is_none()
+            && self.port.is_none()
+            && self.process_name.is_none()
+            && self.extra.is_empty()
+    }
+}
+
+impl SingBoxConfig {
+    /// Replaces the proxy outbounds with the `vless`, `vmess`, `trojan` and `shadowsocks`
+    /// outbounds of an Xray config the way [`import_links`](Self::import_links) does, and
+    /// appends its routing rules to `route.rules`. Balancers become urltests, `freedom`
+    /// maps to the direct outbound and `blackhole` to the `reject` action, and the first
+    /// outbound, where Xray sends unmatched traffic, becomes `route.final`. The template
+    /// keeps its inbounds and DNS.
+    ///
+    /// Fields, rules and outbounds without a sing-box counterpart are listed in
+    /// [`XrayImport::unmapped`]; a rule is only imported if all of its conditions are.
+    pub fn import_xray(&mut self, profile: &XrayProfile) -> XrayImport {
+        let mut import = XrayImport::default();
+        for key in profile.extra.keys() {
+            import.unmapped.push(Unmapped { path: key.clone(), reason: "not imported; the template's is kept".to_string() });
+        }
+
+        // Xray refers to outbounds by tag
+        let mut targets: HashMap<String, ImportedTarget> = HashMap::new();
+        let direct = self.outbounds().iter()
+            .find(|out| matches!(out, Outbound::Direct(_)))
+            .map(|out| out.tag().to_string());
+        let mut positions = Vec::new();
+        let mut links = Vec::new();
+        let mut link_unmapped = Vec::new();
+        for (i, outbound) in profile.outbounds.iter().enumerate() {
+            let path = format!("outbounds[{}]", i);
+            let tag = outbound.get("tag").and_then(Value::as_str).unwrap_or_default().to_string();
+            let target = match outbound.get("protocol").and_then(Value::as_str) {
+                Some("freedom") => match &direct {
+                    Some(direct) => ImportedTarget::Outbound(direct.clone()),
+                    None => {
+                        import.unmapped.push(Unmapped { path, reason: "the template has no direct outbound".to_string() });
+                        continue;
+                    }
+                },
+                Some("blackhole") => ImportedTarget::Reject,
+                Some("dns") => ImportedTarget::Dns,
+                _ => {
+                    positions.push((i, tag.clone()));
+                    match outbound_to_link(outbound) {
+                        Ok((link, unmapped)) => {
+                            links.push((tag, Ok(link)));
+                            link_unmapped.push(unmapped);
+                        }
+                        Err(reason) => {
+                            links.push((tag, Err(reason)));
+                            link_unmapped.push(Vec::new());
+                        }
+                    }
+                    continue;
+                }
+            };
+            // Settings such as freedom's domainStrategy or blackhole's response have no
+            // counterpart in the outbound they map to
+            let mut fields = Fields::new(path, outbound.clone());
+            fields.take("tag");
+            fields.take("protocol");
+            fields.finish(&mut import.unmapped);
+            targets.insert(tag, target);
+        }
+
+        import.outbounds = self.import_parsed(links);
+        let mut imported = import.outbounds.imported.iter();
+        for (j, ((i, tag), unmapped)) in positions.iter().zip(link_unmapped).enumerate() {
+            if import.outbounds.skipped.iter().any(|skipped| skipped.line == j + 1) {
+                continue;
+            }
+            import.unmapped.extend(unmapped.into_iter().map(|unmapped| Unmapped {
+                path: format!("outbounds[{}].{}", i, unmapped.path),
+                reason: unmapped.reason,
+            }));
+            if let Some(imported) = imported.next() {
+                targets.insert(tag.clone(), ImportedTarget::Outbound(imported.clone()));
+            }
+        }
+        for skipped in import.outbounds.skipped.iter_mut() {
+            skipped.line = positions[skipped.line - 1].0 + 1;
+        }
+        if import.outbounds.imported.is_empty() {
+            return import;
+        }
+
+        // Balancers pick outbounds by tag prefix and probe them with the observatory
+        let mut observatory = Fields::new("observatory", profile.observatory.clone().unwrap_or_default());
+        let url = observatory.string("probeURL");
+        let interval = observatory.string("probeInterval");
+        observatory.take("subjectSelector");
+        observatory.take("enableConcurrency");
+        observatory.finish(&mut import.unmapped);
+
+        let mut balancers: HashMap<String, String> = HashMap::new();
+        let mut taken: Vec<String> = self.outbounds().iter().map(|out| out.tag().to_string()).collect();
+        for (i, balancer) in profile.routing.balancers.iter().enumerate() {
+            let path = format!("routing.balancers[{}]", i);
+            let mut members: Vec<String> = Vec::new();
+            for (_, tag) in &positions {
+                if balancer.selector.iter().any(|prefix| tag.starts_with(prefix.as_str()))
+                    && let Some(ImportedTarget::Outbound(member)) = targets.get(tag)
+                    && !members.contains(member) {
+                    members.push(member.clone());
+                }
+            }
+            if members.is_empty() {
+                import.unmapped.push(Unmapped { path, reason: "none of its outbounds were imported".to_string() });
+                continue;
+            }
+            let strategy = balancer.strategy.as_ref()
+                .and_then(|strategy| strategy.get("type"))
+                .and_then(Value::as_str)
+                .unwrap_or("random");
+            if !matches!(strategy, "leastPing" | "leastLoad") {
+                import.unmapped.push(Unmapped {
+                    path: format!("{}.strategy", path),
+                    reason: format!("sing-box has no {} balancing; the urltest picks the fastest outbound", strategy),
+                });
+            }
+            for key in balancer.extra.keys() {
+                import.unmapped.push(Unmapped { path: format!("{}.{}", path, key), reason: "no sing-box equivalent".to_string() });
+            }
+
+            let tag = unique_tag(&balancer.tag, &taken);
+            taken.push(tag.clone());
+            balancers.insert(balancer.tag.clone(), tag.clone());
+            self.put_outbound(Outbound::UrlTest(UrlTestOutbound {
+                outbounds: members,
+                url: url.clone(),
+                interval: interval.clone(),
+                tag: tag.clone(),
+                ..Default::default()
+            }));
+            import.groups.push(tag);
+        }
+
+        if let Some(first) = profile.outbounds.first() {
+            match first.get("tag").and_then(Value::as_str).and_then(|tag| targets.get(tag)) {
+                Some(ImportedTarget::Outbound(tag)) => self.route.final_field = Some(tag.clone()),
+                Some(_) => import.unmapped.push(Unmapped {
+                    path: "outbounds[0]".to_string(),
+                    reason: "sing-box needs an outbound for unmatched traffic".to_string(),
+                }),
+                None => {}
+            }
+        }
+
+        if let Some(strategy) = &profile.routing.domain_strategy && strategy != "AsIs" {
+            import.unmapped.push(Unmapped {
+                path: "routing.domainStrategy".to_string(),
+                reason: "sing-box only resolves domains for IP rules after a resolve action".to_string(),
+            });
+        }
+        for key in profile.routing.extra.keys() {
+            import.unmapped.push(Unmapped { path: format!("routing.{}", key), reason: "no sing-box equivalent".to_string() });
+        }
+        for (i, rule) in profile.routing.rules.iter().enumerate() {
+            let path = format!("routing.rules[{}]", i);
+            let Some(rule) = route_rule_from_xray(rule, &path, &targets, &balancers, &mut import.unmapped) else {
+                continue;
+            };
+            if !rule.matches_all() {
+                self.route.rules.push(rule);
+                import.rules += 1;
+                continue;
+            }
+            // Rules after one that matches everything are never reached
+            match rule.outbound {
+                Some(outbound) => self.route.final_field = Some(outbound),
+                None => import.unmapped.push(Unmapped { path, reason: "sing-box needs an outbound for unmatched traffic".to_string() }),
+            }
+            break;
+        }
+
+        import
+    }
+}
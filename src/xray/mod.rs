@@ -0,0 +1,374 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::models::Unmapped;
+use crate::subscription::ImportReport;
+use crate::url_parser::{Host, ShareLink};
+
+mod export;
+mod import;
+pub use export::*;
+
+
+/// The parts of an Xray-core config that describe outbounds and routing
+#[derive(Deserialize, Debug, Default)]
+pub struct XrayProfile {
+    #[serde(default)]
+    pub outbounds: Vec<Map<String, Value>>,
+    #[serde(default)]
+    pub routing: XrayRouting,
+    /// Probe settings of the `leastPing` balancers
+    pub observatory: Option<Map<String, Value>>,
+    /// Inbounds, DNS, log and the like, which the template provides instead
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct XrayRouting {
+    pub domain_strategy: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<Map<String, Value>>,
+    #[serde(default)]
+    pub balancers: Vec<XrayBalancer>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct XrayBalancer {
+    pub tag: String,
+    /// Prefixes of the outbound tags to balance over
+    #[serde(default)]
+    pub selector: Vec<String>,
+    pub strategy: Option<Map<String, Value>>,
+    /// `fallbackTag` and the like
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct XrayImport {
+    /// Outcome of the proxy outbounds; `line` is the 1-based position in `outbounds`
+    pub outbounds: ImportReport,
+    /// Tags of the urltest outbounds made from balancers
+    pub groups: Vec<String>,
+    /// Number of routing rules added to `route.rules`
+    pub rules: usize,
+    pub unmapped: Vec<Unmapped>,
+}
+
+pub fn parse_xray(json: &str) -> Result<XrayProfile, Error> {
+    serde_json::from_str(json).map_err(Error::XrayConfig)
+}
+
+/// An object of an Xray config whose keys are taken out as they are mapped, so that
+/// whatever is left can be reported
+struct Fields {
+    path: String,
+    map: Map<String, Value>,
+    /// Fields that were taken but had the wrong type, reported by `finish`
+    invalid: Vec<Unmapped>,
+}
+
+impl Fields {
+    fn new(path: impl Into<String>, map: Map<String, Value>) -> Fields {
+        Fields { path: path.into(), map, invalid: Vec::new() }
+    }
+
+    fn child_path(&self, key: &str) -> String {
+        if self.path.is_empty() { key.to_string() } else { format!("{}.{}", self.path, key) }
+    }
+
+    fn take(&mut self, key: &str) -> Option<Value> {
+        self.map.remove(key)
+    }
+
+    fn reject(&mut self, path: String, reason: &str) {
+        self.invalid.push(Unmapped { path, reason: reason.to_string() });
+    }
+
+    /// A string or number field as text; Xray accepts ports both ways. Empty
+    /// strings, arrays and objects are reported instead
+    fn string(&mut self, key: &str) -> Option<String> {
+        match self.map.remove(key)? {
+            Value::String(s) if !s.is_empty() => Some(s),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Null => None,
+            Value::String(_) => {
+                self.reject(self.child_path(key), "expected a non-empty string");
+                None
+            }
+            _ => {
+                self.reject(self.child_path(key), "expected a string or number");
+                None
+            }
+        }
+    }
+
+    /// A list of strings, or a single comma separated one; items of another type
+    /// are reported
+    fn strings(&mut self, key: &str) -> Vec<String> {
+        match self.map.remove(key) {
+            Some(Value::Array(items)) => {
+                let mut strings = Vec::new();
+                for (index, item) in items.into_iter().enumerate() {
+                    match item {
+                        Value::String(s) if !s.is_empty() => strings.push(s),
+                        Value::Number(n) => strings.push(n.to_string()),
+                        Value::String(_) | Value::Null => {}
+                        _ => self.reject(format!("{}[{}]", self.child_path(key), index), "expected a string or number"),
+                    }
+                }
+                strings
+            }
+            Some(Value::String(s)) => s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect(),
+            Some(Value::Number(n)) => vec![n.to_string()],
+            Some(Value::Null) | None => Vec::new(),
+            Some(_) => {
+                self.reject(self.child_path(key), "expected a string or a list of strings");
+                Vec::new()
+            }
+        }
+    }
+
+    fn flag(&mut self, key: &str) -> bool {
+        matches!(self.string(key).as_deref(), Some("true" | "1"))
+    }
+
+    /// A nested object, empty when missing
+    fn object(&mut self, key: &str) -> Fields {
+        let path = self.child_path(key);
+        match self.map.remove(key) {
+            Some(Value::Object(map)) => Fields::new(path, map),
+            _ => Fields::new(path, Map::new()),
+        }
+    }
+
+    /// The first object of a list such as `vnext` or `users`; the others are reported
+    fn first(&mut self, key: &str, unmapped: &mut Vec<Unmapped>) -> Fields {
+        let path = self.child_path(key);
+        let mut items = match self.map.remove(key) {
+            Some(Value::Array(items)) => items,
+            _ => Vec::new(),
+        };
+        if items.len() > 1 {
+            unmapped.push(Unmapped {
+                path: format!("{}[1..]", path),
+                reason: "a sing-box outbound has a single server and user".to_string(),
+            });
+        }
+        match items.drain(..).next() {
+            Some(Value::Object(map)) => Fields::new(format!("{}[0]", path), map),
+            _ => Fields::new(format!("{}[0]", path), Map::new()),
+        }
+    }
+
+    /// Reports the keys nothing took, down to the fields of nested objects; empty
+    /// values are skipped
+    fn finish(self, unmapped: &mut Vec<Unmapped>) {
+        unmapped.extend(self.invalid.iter().cloned());
+        for (key, value) in self.map.iter() {
+            match value {
+                Value::Null => {}
+                Value::Array(items) if items.is_empty() => {}
+                Value::Object(map) => Fields::new(self.child_path(key), map.clone()).finish(unmapped),
+                _ => unmapped.push(Unmapped { path: self.child_path(key), reason: "no sing-box equivalent".to_string() }),
+            }
+        }
+    }
+}
+
+/// Translates an Xray `vless`, `vmess`, `trojan` or `shadowsocks` outbound into the
+/// share link parameters it corresponds to, so the outbound is built by the same code
+/// as for links. Fields that have no parameter are returned with paths relative to
+/// the outbound.
+pub fn outbound_to_link(outbound: &Map<String, Value>) -> Result<(ShareLink, Vec<Unmapped>), Error> {
+    let mut unmapped = Vec::new();
+    let mut fields = Fields::new("", outbound.clone());
+    let protocol = fields.string("protocol").ok_or_else(|| Error::invalid_link("protocol", "xray outbounds must have a protocol"))?;
+    let scheme = match protocol.as_str() {
+        "vless" | "vmess" | "trojan" => protocol.clone(),
+        "shadowsocks" => "ss".to_string(),
+        other => return Err(Error::UnsupportedProtocol(other.to_string())),
+    };
+    let fragment = fields.string("tag");
+
+    let mut query = Vec::new();
+    let mut push = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            query.push((key.to_string(), value));
+        }
+    };
+
+    // VLESS and VMess list servers under `vnext` with their users, Trojan and
+    // Shadowsocks under `servers`
+    let mut settings = fields.object("settings");
+    let (mut server, user_info) = match protocol.as_str() {
+        "vless" | "vmess" => {
+            let mut server = settings.first("vnext", &mut unmapped);
+            let mut user = server.first("users", &mut unmapped);
+            let id = user.string("id");
+            if protocol == "vless" {
+                if let Some(encryption) = user.string("encryption").filter(|encryption| encryption != "none") {
+                    return Err(Error::invalid_link("encryption", format!("unsupported vless encryption {:?}", encryption)));
+                }
+                push("flow", user.string("flow"));
+            } else {
+                push("aid", user.string("alterId"));
+                push("encryption", user.string("security"));
+            }
+            // Policy levels and stats names mean nothing to sing-box
+            user.take("level");
+            user.take("email");
+            user.finish(&mut unmapped);
+            (server, id)
+        }
+        _ => {
+            let mut server = settings.first("servers", &mut unmapped);
+            let password = server.string("password");
+            let user_info = match protocol.as_str() {
+                "shadowsocks" => server.string("method").map(|method| format!("{}:{}", method, password.unwrap_or_default())),
+                _ => password,
+            };
+            server.take("level");
+            server.take("email");
+            (server, user_info)
+        }
+    };
+    let host: Host = server.string("address")
+        .ok_or_else(|| Error::invalid_link("address", "xray outbounds must have a server address"))?
+        .parse()?;
+    let port = match server.string("port") {
+        Some(port) => Some(port.parse::<u16>()
+            .map_err(|_| Error::invalid_link("port", format!("invalid port {:?}", port)))?),
+        None => None,
+    };
+    server.finish(&mut unmapped);
+    settings.finish(&mut unmapped);
+
+    let mut stream = fields.object("streamSettings");
+    if scheme != "ss" {
+        stream_params(&mut stream, &mut push, &mut unmapped)?;
+    }
+    stream.finish(&mut unmapped);
+
+    if fields.object("mux").flag("enabled") {
+        unmapped.push(Unmapped { path: "mux".to_string(), reason: "multiplexing is not carried over".to_string() });
+    }
+    fields.finish(&mut unmapped);
+
+    let link = ShareLink { scheme, user_info, host, port, path: String::new(), query, fragment };
+    Ok((link, unmapped))
+}
+
+/// Maps `security` with its settings and `network` with its settings onto `security`,
+/// `sni`, `alpn`, `fp`, `pbk`, `sid`, `allowInsecure`, `type`, `path`, `host`,
+/// `serviceName`, `mode` and `headerType`
+fn stream_params(stream: &mut Fields, push: &mut impl FnMut(&str, Option<String>), unmapped: &mut Vec<Unmapped>) -> Result<(), Error> {
+    let security = stream.string("security").unwrap_or_else(|| "none".to_string());
+    match security.as_str() {
+        "none" => push("security", Some("none".to_string())),
+        "tls" => {
+            let mut tls = stream.object("tlsSettings");
+            push("security", Some("tls".to_string()));
+            push("sni", tls.string("serverName"));
+            push("alpn", Some(tls.strings("alpn").join(",")).filter(|alpn| !alpn.is_empty()));
+            push("fp", tls.string("fingerprint"));
+            if tls.flag("allowInsecure") {
+                push("allowInsecure", Some("1".to_string()));
+            }
+            tls.finish(unmapped);
+        }
+        "reality" => {
+            let mut reality = stream.object("realitySettings");
+            push("security", Some("reality".to_string()));
+            push("sni", reality.string("serverName"));
+            push("fp", reality.string("fingerprint"));
+            push("pbk", reality.string("publicKey"));
+            push("sid", reality.string("shortId"));
+            reality.finish(unmapped);
+        }
+        other => return Err(Error::UnsupportedSecurity(other.to_string())),
+    }
+
+    let network = stream.string("network").unwrap_or_else(|| "tcp".to_string());
+    let mut settings = match network.as_str() {
+        "tcp" | "raw" => {
+            let mut settings = stream.object(if network == "raw" { "rawSettings" } else { "tcpSettings" });
+            let mut header = settings.object("header");
+            push("type", Some("tcp".to_string()));
+            match header.string("type").as_deref() {
+                None | Some("none") => {}
+                Some("http") => {
+                    let mut request = header.object("request");
+                    let mut headers = request.object("headers");
+                    push("headerType", Some("http".to_string()));
+                    push("path", request.strings("path").into_iter().next());
+                    push("host", Some(headers.strings("Host").join(",")).filter(|host| !host.is_empty()));
+                    headers.finish(unmapped);
+                    request.finish(unmapped);
+                }
+                Some(other) => return Err(Error::UnsupportedTransport(format!("tcp with header type {}", other))),
+            }
+            header.finish(unmapped);
+            settings
+        }
+        "ws" | "websocket" => {
+            let mut settings = stream.object("wsSettings");
+            let mut headers = settings.object("headers");
+            push("type", Some("ws".to_string()));
+            // The path may carry `?ed=` early data, which the link parser splits off
+            push("path", settings.string("path"));
+            push("host", settings.string("host").or_else(|| headers.string("Host")));
+            headers.finish(unmapped);
+            settings
+        }
+        "grpc" | "gun" => {
+            let mut settings = stream.object("grpcSettings");
+            push("type", Some("grpc".to_string()));
+            push("serviceName", settings.string("serviceName"));
+            if settings.flag("multiMode") {
+                unmapped.push(Unmapped {
+                    path: settings.child_path("multiMode"),
+                    reason: "sing-box only speaks gun mode".to_string(),
+                });
+            }
+            settings
+        }
+        "httpupgrade" => {
+            let mut settings = stream.object("httpupgradeSettings");
+            push("type", Some("httpupgrade".to_string()));
+            push("path", settings.string("path"));
+            push("host", settings.string("host"));
+            settings
+        }
+        "xhttp" | "splithttp" => {
+            let mut settings = stream.object(if network == "xhttp" { "xhttpSettings" } else { "splithttpSettings" });
+            push("type", Some("xhttp".to_string()));
+            push("path", settings.string("path"));
+            push("host", settings.string("host"));
+            push("mode", settings.string("mode"));
+            settings
+        }
+        "h2" | "http" => {
+            let mut settings = stream.object("httpSettings");
+            push("type", Some("http".to_string()));
+            push("path", settings.string("path"));
+            push("host", Some(settings.strings("host").join(",")).filter(|host| !host.is_empty()));
+            settings
+        }
+        "quic" => {
+            push("type", Some("quic".to_string()));
+            stream.object("quicSettings")
+        }
+        other => return Err(Error::UnsupportedTransport(other.to_string())),
+    };
+    // Only matters to servers
+    settings.take("acceptProxyProtocol");
+    settings.finish(unmapped);
+    Ok(())
+}
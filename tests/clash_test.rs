@@ -0,0 +1,281 @@
+mod common;
+
+use lessvless::clash::{link_to_proxy, parse_clash, proxy_to_link, CLASH_GROUP_NAME};
+use lessvless::models::SingBoxConfig;
+use lessvless::url_parser::parse_share_link;
+use lessvless::Error;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+mixed-port: 7890
+proxies:
+  - name: Reality
+    type: vless
+    server: a.host.tld
+    port: 443
+    uuid: 0b5f3e2c-5a6e-4c0f-9a3b-7e9d1c2b4a11
+    network: tcp
+    tls: true
+    flow: xtls-rprx-vision
+    servername: www.microsoft.com
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: iBXtaHGkwadJMtkWYZxMRfqLLAuDvTHKsHQiLFXXJniM
+      short-id: 6ba85179e30d4fc2
+  - name: VMess WS
+    type: vmess
+    server: 2001:db8::1
+    port: "8443"
+    uuid: 6c1d2b8a-3f4e-4a5b-8c9d-0e1f2a3b4c5d
+    alterId: 0
+    cipher: auto
+    tls: true
+    skip-cert-verify: true
+    network: ws
+    ws-opts:
+      path: /ws
+      headers:
+        Host: cdn.host.tld
+      max-early-data: 2048
+  - name: Trojan gRPC
+    type: trojan
+    server: c.host.tld
+    port: 443
+    password: secret
+    sni: c.host.tld
+    alpn: [h2, http/1.1]
+    network: grpc
+    grpc-opts:
+      grpc-service-name: tunnel
+  - name: SS
+    type: ss
+    server: 203.0.113.7
+    port: 8388
+    cipher: aes-256-gcm
+    password: secret
+    plugin: obfs
+    plugin-opts:
+      mode: http
+      host: bing.com
+  - name: Hy2
+    type: hysteria2
+    server: e.host.tld
+    port: 443
+    ports: 20000-30000
+    password: auth
+    obfs: salamander
+    obfs-password: salt
+  - name: TUIC
+    type: tuic
+    server: f.host.tld
+    port: 443
+    uuid: 7d2e3c9b-4f5a-4b6c-9d0e-1f2a3b4c5d6e
+    password: pass
+    congestion-controller: bbr
+    udp-relay-mode: native
+  - name: Socks
+    type: socks5
+    server: g.host.tld
+    port: 1080
+proxy-groups:
+  - name: Proxy
+    type: select
+    proxies: [Auto, Reality, VMess WS, Socks, DIRECT, REJECT]
+  - name: Auto
+    type: url-test
+    proxies: [Reality, Trojan gRPC, Hy2]
+    url: https://www.gstatic.com/generate_204
+    interval: 300
+    tolerance: 50
+  - name: Balance
+    type: load-balance
+    proxies: [SS, TUIC]
+  - name: Dead
+    type: fallback
+    proxies: [Socks, REJECT]
+rules:
+  - MATCH,Proxy
+"#;
+
+    fn outbound<'a>(json: &'a serde_json::Value, tag: &str) -> &'a serde_json::Value {
+        json["outbounds"].as_array().unwrap().iter().find(|out| out["tag"] == tag).unwrap()
+    }
+
+    #[test]
+    fn test_import_clash_proxies() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        let import = config.import_clash(&parse_clash(PROFILE)?, false);
+
+        assert_eq!(import.proxies.imported, vec!["Reality", "VMess WS", "Trojan gRPC", "SS", "Hy2", "TUIC"]);
+        assert_eq!(import.proxies.skipped.len(), 1);
+        assert_eq!(import.proxies.skipped[0].line, 7);
+        assert_eq!(import.proxies.skipped[0].link, "Socks");
+        assert!(matches!(import.proxies.skipped[0].reason, Error::UnsupportedProtocol(ref p) if p == "socks5"));
+        assert!(import.groups.is_empty());
+
+        let json = serde_json::to_value(&config)?;
+        let reality = outbound(&json, "Reality");
+        assert_eq!(reality["type"], "vless");
+        assert_eq!(reality["flow"], "xtls-rprx-vision");
+        assert_eq!(reality["tls"]["server_name"], "www.microsoft.com");
+        assert_eq!(reality["tls"]["reality"]["public_key"], "iBXtaHGkwadJMtkWYZxMRfqLLAuDvTHKsHQiLFXXJniM");
+        assert_eq!(reality["tls"]["reality"]["short_id"], "6ba85179e30d4fc2");
+        assert_eq!(reality["tls"]["utls"]["fingerprint"], "chrome");
+
+        let vmess = outbound(&json, "VMess WS");
+        assert_eq!(vmess["server"], "2001:db8::1");
+        assert_eq!(vmess["server_port"], 8443);
+        assert_eq!(vmess["tls"]["insecure"], true);
+        assert_eq!(vmess["transport"]["type"], "ws");
+        assert_eq!(vmess["transport"]["path"], "/ws");
+        assert_eq!(vmess["transport"]["headers"]["Host"], "cdn.host.tld");
+        assert_eq!(vmess["transport"]["max_early_data"], 2048);
+
+        let trojan = outbound(&json, "Trojan gRPC");
+        assert_eq!(trojan["tls"]["alpn"], serde_json::json!(["h2", "http/1.1"]));
+        assert_eq!(trojan["transport"]["service_name"], "tunnel");
+
+        let ss = outbound(&json, "SS");
+        assert_eq!(ss["plugin"], "obfs-local");
+        assert_eq!(ss["plugin_opts"], "obfs=http;obfs-host=bing.com");
+
+        let hy2 = outbound(&json, "Hy2");
+        assert_eq!(hy2["server_ports"], serde_json::json!(["20000:30000"]));
+        assert_eq!(hy2["obfs"]["password"], "salt");
+
+        let tuic = outbound(&json, "TUIC");
+        assert_eq!(tuic["password"], "pass");
+        assert_eq!(tuic["congestion_control"], "bbr");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_clash_groups() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        let import = config.import_clash(&parse_clash(PROFILE)?, true);
+
+        assert_eq!(import.groups, vec!["Proxy", "Auto"]);
+        let skipped: Vec<&str> = import.skipped_groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(skipped, vec!["Balance", "Dead"]);
+
+        let json = serde_json::to_value(&config)?;
+        let proxy = outbound(&json, "Proxy");
+        assert_eq!(proxy["type"], "selector");
+        assert_eq!(proxy["outbounds"], serde_json::json!(["Auto", "Reality", "VMess WS", "direct-out"]));
+        let auto = outbound(&json, "Auto");
+        assert_eq!(auto["type"], "urltest");
+        assert_eq!(auto["outbounds"], serde_json::json!(["Reality", "Trojan gRPC", "Hy2"]));
+        assert_eq!(auto["url"], "https://www.gstatic.com/generate_204");
+        assert_eq!(auto["interval"], "300s");
+        assert_eq!(auto["tolerance"], 50);
+        assert_eq!(json["route"]["final"], "Proxy");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_proxy_to_link() -> Result<(), Box<dyn std::error::Error>> {
+        let profile = parse_clash("proxies:\n  - {name: h2, type: vmess, server: h.tld, port: 443, uuid: id, network: h2, h2-opts: {host: [a.tld, b.tld], path: /p}}\n")?;
+        let link = proxy_to_link(&profile.proxies[0])?;
+        assert_eq!(link.param("type"), Some("http"));
+        assert_eq!(link.param("host"), Some("a.tld,b.tld"));
+        assert_eq!(link.param("path"), Some("/p"));
+        assert_eq!(link.param("security"), Some("none"));
+        assert_eq!(link.fragment.as_deref(), Some("h2"));
+
+        let profile = parse_clash("proxies:\n  - {name: x, type: trojan, port: 443, password: p}\n")?;
+        let err = proxy_to_link(&profile.proxies[0]).unwrap_err();
+        assert!(matches!(err, Error::InvalidLink { ref field, .. } if field == "server"), "{:?}", err);
+
+        let err = parse_clash("proxies: {not: a list}").unwrap_err();
+        assert!(matches!(err, Error::ClashProfile(_)), "{:?}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_clash() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        config.enrich_from_url("vless://0b5f3e2c-5a6e-4c0f-9a3b-7e9d1c2b4a11@a.host.tld:443?security=reality&pbk=iBXtaHGkwadJMtkWYZxMRfqLLAuDvTHKsHQiLFXXJniM&sid=6ba85179e30d4fc2&sni=www.microsoft.com&fp=chrome&flow=xtls-rprx-vision".to_string())?;
+        let export = config.to_clash();
+        assert!(export.unmapped.is_empty(), "{:?}", export.unmapped);
+
+        let yaml = serde_yaml::to_string(&export.config)?;
+        assert!(yaml.starts_with("mixed-port: 7890\nproxies:\n- name: wh3tduwc\n  type: vless\n"), "{}", yaml);
+        assert_eq!(export.config.proxy_groups.len(), 1);
+        assert_eq!(export.config.proxy_groups[0].name, CLASH_GROUP_NAME);
+        assert_eq!(export.config.proxy_groups[0].proxies, vec!["wh3tduwc"]);
+
+        // The dns-out rule is left to mihomo's resolver
+        let rules = &export.config.rules;
+        assert_eq!(rules[0], "IP-CIDR,10.0.0.0/8,DIRECT,no-resolve");
+        assert_eq!(rules[7], "IP-CIDR,fe80::/10,DIRECT,no-resolve");
+        assert_eq!(&rules[8..], ["DOMAIN-SUFFIX,lan,DIRECT", "DOMAIN-SUFFIX,local,DIRECT", "DOMAIN-SUFFIX,home,DIRECT", "MATCH,PROXY"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_clash_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        config.import_clash(&parse_clash(PROFILE)?, true);
+        let export = config.to_clash();
+        assert!(export.unmapped.is_empty(), "{:?}", export.unmapped);
+
+        let groups: Vec<(&str, &str)> = export.config.proxy_groups.iter()
+            .map(|group| (group.name.as_str(), group.kind.as_str()))
+            .collect();
+        assert_eq!(groups, vec![("Proxy", "select"), ("Auto", "url-test")]);
+        assert_eq!(export.config.proxy_groups[0].proxies, vec!["Auto", "Reality", "VMess WS", "DIRECT"]);
+        assert_eq!(export.config.proxy_groups[1].interval, Some(300));
+        assert_eq!(export.config.rules.last().unwrap(), "MATCH,Proxy");
+
+        // Importing the rendered profile gives back the same outbounds
+        let mut reimported = default_config()?;
+        reimported.import_clash(&parse_clash(&serde_yaml::to_string(&export.config)?)?, true);
+        assert_eq!(serde_json::to_value(reimported.outbounds())?, serde_json::to_value(config.outbounds())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_clash_rules() -> Result<(), Box<dyn std::error::Error>> {
+        let mut json = serde_json::to_value(default_config()?)?;
+        json["route"]["rules"] = serde_json::json!([
+            { "domain_suffix": ["example.com", "example.org"], "port": 443, "outbound": "direct-out" },
+            { "process_name": ["curl"], "action": "reject" },
+            { "ip_is_private": true, "outbound": "direct-out" },
+            { "inbound": "tun-in", "outbound": "direct-out" },
+            { "rule_set": "geosite-ads", "action": "reject" },
+            { "domain_suffix": ["missing.tld"], "outbound": "missing" },
+            { "ip_is_private": false, "outbound": "direct-out" },
+        ]);
+        json["route"]["final"] = serde_json::json!("direct-out");
+        let config: SingBoxConfig = serde_json::from_value(json)?;
+        let export = config.to_clash();
+
+        let rules = &export.config.rules;
+        assert_eq!(rules[0], "AND,((OR,((DOMAIN-SUFFIX,example.com),(DOMAIN-SUFFIX,example.org))),(DST-PORT,443)),DIRECT");
+        assert_eq!(rules[1], "PROCESS-NAME,curl,REJECT");
+        assert_eq!(rules[2], "IP-CIDR,10.0.0.0/8,DIRECT,no-resolve");
+        assert_eq!(rules.last().unwrap(), "MATCH,DIRECT");
+
+        let unmapped: Vec<String> = export.unmapped.iter().map(|unmapped| unmapped.path.clone()).collect();
+        assert_eq!(unmapped, vec![
+            "route.rules[3].inbound",
+            "route.rules[4].rule_set",
+            "route.rules[5].outbound",
+            "route.rules[6].ip_is_private",
+        ]);
+
+        let link = parse_share_link("vless://uuid@v.tld:443?security=tls&type=quic")?;
+        let err = link_to_proxy(&link, "quic").unwrap_err();
+        assert!(matches!(err, Error::UnsupportedTransport(ref t) if t == "quic"), "{:?}", err);
+
+        Ok(())
+    }
+}
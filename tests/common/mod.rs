@@ -0,0 +1,16 @@
+// Each test crate compiles its own copy and uses only some of the helpers
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use lessvless::models::SingBoxConfig;
+use lessvless::utils::find_git_root;
+
+/// The `config/default.json` template the tests start from
+pub fn default_config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(find_git_root()?.canonicalize()?.join("config").join("default.json"))
+}
+
+pub fn default_config() -> Result<SingBoxConfig, Box<dyn std::error::Error>> {
+    Ok(SingBoxConfig::from_file(default_config_path()?)?)
+}
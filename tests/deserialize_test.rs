@@ -1,5 +1,8 @@
+mod common;
 
-use lessvless::models::{Route,SingBoxConfig};
+use lessvless::models::{Outbound,Route,SingBoxConfig};
+use lessvless::Error;
+use common::default_config_path;
 
 mod tests {
     use super::*;
@@ -40,7 +43,7 @@ mod tests {
                 ]
         }"#;
 
-        let expected_route: Route = serde_json::from_str(rule_str)?;
+        let _expected_route: Route = serde_json::from_str(rule_str)?;
 
         Ok(())
     }
@@ -154,7 +157,215 @@ mod tests {
 
         }"#;
 
-        let expected_route: SingBoxConfig = serde_json::from_str(rule_str)?;
+        let config: SingBoxConfig = serde_json::from_str(rule_str)?;
+        assert_eq!(config.route_final(), Some("wh3tduwc"));
+        let targets: Vec<Option<&str>> = config.route_rules().iter().map(|rule| rule.outbound()).collect();
+        assert_eq!(targets, vec![Some("dns-out"), Some("direct-out"), Some("direct-out")]);
+        assert!(config.route_rules().iter().all(|rule| rule.action().is_none()));
+        assert!(config.outbounds().iter().all(|out| out.members().is_empty()));
+
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_roundtrip_unknown_fields() -> Result<(), Box<dyn std::error::Error>> {
+
+        let config_str = r#"
+            {
+              "dns": {
+                "final": "cloudflare-doh",
+                "independent_cache": true,
+                "servers": [
+                  {
+                    "address": "https://1.1.1.1/dns-query",
+                    "detour": "direct-out",
+                    "tag": "cloudflare-doh"
+                  }
+                ]
+              },
+              "endpoints": [],
+              "experimental": {
+                "cache_file": {
+                  "enabled": true
+                }
+              },
+              "inbounds": [
+                {
+                  "listen": "127.0.0.1",
+                  "listen_port": 7890,
+                  "sniff": true,
+                  "tag": "mixed-in",
+                  "type": "mixed"
+                }
+              ],
+              "log": {
+                "level": "info",
+                "output": "box.log"
+              },
+              "ntp": {
+                "enabled": false
+              },
+              "outbounds": [
+                {
+                  "domain_strategy": "prefer_ipv4",
+                  "tag": "direct-out",
+                  "type": "direct"
+                },
+                {
+                  "tag": "dns-out",
+                  "type": "dns"
+                },
+                {
+                  "connect_timeout": "5s",
+                  "packet_encoding": "xudp",
+                  "server": "example_server",
+                  "server_port": 8080,
+                  "tag": "proxy",
+                  "tls": {
+                    "alpn": ["h2"],
+                    "enabled": true,
+                    "reality": {
+                      "enabled": true,
+                      "public_key": "example_public_key",
+                      "short_id": "example_short_id"
+                    },
+                    "server_name": "example_server_name.ru",
+                    "utls": {
+                      "enabled": true,
+                      "fingerprint": "chrome"
+                    }
+                  },
+                  "type": "vless",
+                  "uuid": "example_uuid"
+                }
+              ],
+              "route": {
+                "auto_detect_interface": true,
+                "final": "proxy",
+                "rule_set": [],
+                "rules": [
+                  {
+                    "outbound": "direct-out",
+                    "network": "udp",
+                    "port": 123
+                  }
+                ]
+              }
+        }"#;
+
+        let config: SingBoxConfig = serde_json::from_str(config_str)?;
+        let json = serde_json::to_value(&config)?;
+
+        assert_eq!(json["inbounds"][0]["listen"], "127.0.0.1");
+        assert_eq!(json["inbounds"][0]["listen_port"], 7890);
+        assert_eq!(json["dns"]["independent_cache"], true);
+        assert_eq!(json["dns"]["servers"][0]["detour"], "direct-out");
+        assert_eq!(json["log"]["output"], "box.log");
+        assert_eq!(json["experimental"]["cache_file"]["enabled"], true);
+        assert_eq!(json["ntp"]["enabled"], false);
+        assert!(json["endpoints"].as_array().unwrap().is_empty());
+        assert_eq!(json["outbounds"][0]["domain_strategy"], "prefer_ipv4");
+        assert_eq!(json["outbounds"][2]["connect_timeout"], "5s");
+        assert_eq!(json["outbounds"][2]["tls"]["alpn"][0], "h2");
+        assert!(json["route"]["rule_set"].as_array().unwrap().is_empty());
+        assert_eq!(json["route"]["rules"][0]["network"], "udp");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_transport() -> Result<(), Box<dyn std::error::Error>> {
+        for transport in [
+            serde_json::json!({ "type": "ws", "path": "/ws", "headers": { "Host": "a.tld" }, "ping_timeout": "5s" }),
+            serde_json::json!({ "type": "grpc", "service_name": "svc", "idle_timeout": "15s" }),
+            serde_json::json!({ "type": "someday", "key": "value" }),
+        ] {
+            let outbound = serde_json::json!({
+                "packet_encoding": "xudp",
+                "server": "host.tld",
+                "server_port": 443,
+                "tag": "proxy",
+                "transport": transport,
+                "type": "vless",
+                "uuid": "uuid"
+            });
+            let parsed: Outbound = serde_json::from_value(outbound)?;
+            assert_eq!(serde_json::to_value(&parsed)?["transport"], transport);
+        }
+
+        Ok(())
+    }
+
+    fn strict_field(outbound: serde_json::Value) -> Option<String> {
+        match Outbound::from_value_strict(outbound) {
+            Err(Error::InvalidField { field, .. }) => Some(field),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_strict_outbound() -> Result<(), Box<dyn std::error::Error>> {
+        let vless = serde_json::json!({ "tag": "proxy", "type": "vless", "server": "host.tld", "server_port": 443, "uuid": "uuid" });
+        assert!(Outbound::from_value_strict(vless.clone()).is_ok());
+
+        let with = |key: &str, value: serde_json::Value| {
+            let mut outbound = vless.clone();
+            outbound[key] = value;
+            outbound
+        };
+        let without = |key: &str| {
+            let mut outbound = vless.clone();
+            outbound.as_object_mut().unwrap().remove(key);
+            outbound
+        };
+        assert_eq!(strict_field(without("uuid")).as_deref(), Some("uuid"));
+        assert_eq!(strict_field(without("server")).as_deref(), Some("server"));
+        assert_eq!(strict_field(without("server_port")).as_deref(), Some("server_port"));
+        assert_eq!(strict_field(without("type")).as_deref(), Some("type"));
+        assert_eq!(strict_field(with("server_port", serde_json::json!("443"))).as_deref(), Some("server_port"));
+        assert_eq!(strict_field(with("tag", serde_json::json!(1))).as_deref(), Some("tag"));
+        assert_eq!(strict_field(with("flow", serde_json::json!(true))).as_deref(), Some("flow"));
+        assert_eq!(strict_field(with("tls", serde_json::json!({ "enabled": "yes" }))).as_deref(), Some("tls.enabled"));
+        assert_eq!(strict_field(with("tls", serde_json::json!({ "enabled": true, "utls": { "enabled": true } }))).as_deref(), Some("tls.utls.fingerprint"));
+
+        // Other types are read by their models, which name the field as well
+        let trojan = serde_json::json!({ "tag": "t", "type": "trojan", "server": "host.tld", "server_port": 443 });
+        assert_eq!(strict_field(trojan).as_deref(), Some("password"));
+        let selector = serde_json::json!({ "tag": "s", "type": "selector", "outbounds": ["a", 1] });
+        assert_eq!(strict_field(selector).as_deref(), Some("outbounds[1]"));
+
+        // The lenient read still fills in placeholders
+        let lenient: Outbound = serde_json::from_value(without("uuid"))?;
+        assert_eq!(serde_json::to_value(&lenient)?["uuid"], "default_uuid");
+        let lenient: Outbound = serde_json::from_value(with("tls", serde_json::json!({ "enabled": "yes" })))?;
+        assert!(serde_json::to_value(&lenient)?.get("tls").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_config() -> Result<(), Box<dyn std::error::Error>> {
+        let config = SingBoxConfig::from_file_strict(default_config_path()?)?;
+        // Tags come out as they were written, without quotes to strip
+        let tags: Vec<&str> = config.outbounds().iter().map(|out| out.tag()).collect();
+        assert_eq!(tags, vec!["direct-out", "dns-out", "wh3tduwc"]);
+        assert!(!serde_json::to_string_pretty(&config)?.contains("\\\""));
+
+        let mut value = serde_json::to_value(&config)?;
+        value["outbounds"][2]["tls"]["reality"]["enabled"] = serde_json::json!("yes");
+        let err = SingBoxConfig::from_value_strict(value.clone()).unwrap_err();
+        assert!(matches!(err, Error::InvalidField { ref field, .. } if field == "outbounds[2].tls.reality.enabled"), "{:?}", err);
+
+        value["outbounds"][2]["tls"]["reality"]["enabled"] = serde_json::json!(true);
+        value["route"]["rules"][0]["port"] = serde_json::json!("53");
+        let err = SingBoxConfig::from_value_strict(value.clone()).unwrap_err();
+        assert!(matches!(err, Error::InvalidField { ref field, .. } if field == "route.rules[0].port"), "{:?}", err);
+
+        value["route"]["rules"][0].as_object_mut().unwrap().remove("port");
+        value.as_object_mut().unwrap().remove("log");
+        let err = SingBoxConfig::from_value_strict(value).unwrap_err();
+        assert_eq!(err.to_string(), "invalid config field log: missing field");
 
         Ok(())
     }
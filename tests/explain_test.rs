@@ -0,0 +1,107 @@
+mod common;
+
+use lessvless::models::{Connection, Explanation, RuleMiss, SingBoxConfig};
+use serde_json::json;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_default() -> Result<(), Box<dyn std::error::Error>> {
+        let config = default_config()?;
+
+        let connection = Connection {
+            domain: Some("foo.lan".to_string()),
+            ip: Some("10.1.2.3".parse()?),
+            port: Some(443),
+            process: Some("firefox".to_string()),
+            ..Connection::default()
+        };
+        assert_eq!(config.explain(&connection), Explanation {
+            rule: Some(1),
+            action: "route".to_string(),
+            outbound: Some("direct-out".to_string()),
+            misses: vec![RuleMiss { index: 0, reasons: vec!["no sniffed protocol to match dns".to_string()] }],
+        });
+
+        let connection = Connection {
+            protocol: Some("tls".to_string()),
+            domain: Some("example.com".to_string()),
+            ip: Some("8.8.8.8".parse()?),
+            ..Connection::default()
+        };
+        let explanation = config.explain(&connection);
+        assert_eq!(explanation.rule, None);
+        assert_eq!(explanation.outbound.as_deref(), Some("wh3tduwc"));
+        let reasons: Vec<&str> = explanation.misses.iter().flat_map(|miss| &miss.reasons).map(String::as_str).collect();
+        assert_eq!(reasons, vec![
+            "protocol tls is not dns",
+            "8.8.8.8 is in no ip_cidr",
+            "example.com is under no domain_suffix",
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_conditions() -> Result<(), Box<dyn std::error::Error>> {
+        let config: SingBoxConfig = serde_json::from_value(json!({
+            "dns": { "final": "system", "servers": [{ "address": "local", "tag": "system" }] },
+            "inbounds": [],
+            "log": { "level": "info" },
+            "outbounds": [
+                { "tag": "direct-out", "type": "direct" },
+                { "tag": "t", "type": "trojan", "server": "t.tld", "server_port": 443, "password": "secret" },
+            ],
+            "route": {
+                "auto_detect_interface": true,
+                "rules": [
+                    { "action": "sniff" },
+                    { "rule_set": ["geosite-ads"], "action": "reject" },
+                    { "inbound": "tun-in", "process_name": ["curl"], "outbound": "t" },
+                    { "domain_suffix": [".corp"], "ip_is_private": true, "port": 443, "outbound": "direct-out" },
+                    { "ip_cidr": ["2001:db8::/32"], "action": "reject", "method": "drop" },
+                ],
+            },
+        }))?;
+
+        // Either destination condition is enough, the port has to match as well
+        let connection = Connection {
+            inbound: Some("tun-in".to_string()),
+            domain: Some("corp".to_string()),
+            ip: Some("192.168.1.1".parse()?),
+            port: Some(443),
+            process: Some("firefox".to_string()),
+            ..Connection::default()
+        };
+        let explanation = config.explain(&connection);
+        assert_eq!(explanation.rule, Some(3));
+        assert_eq!(explanation.misses, vec![
+            RuleMiss { index: 0, reasons: vec!["matches, but sniff does not pick an outbound".to_string()] },
+            RuleMiss { index: 1, reasons: vec!["rule_set is not simulated".to_string()] },
+            RuleMiss { index: 2, reasons: vec!["process firefox is not in process_name".to_string()] },
+        ]);
+
+        let connection = Connection {
+            domain: Some("corp".to_string()),
+            ip: Some("2001:db8::1".parse()?),
+            port: Some(80),
+            ..Connection::default()
+        };
+        let explanation = config.explain(&connection);
+        assert_eq!(explanation.rule, Some(4));
+        assert_eq!(explanation.action, "reject");
+        assert_eq!(explanation.outbound, None);
+        assert_eq!(explanation.misses[1].reasons, vec!["rule_set is not simulated"]);
+        assert_eq!(explanation.misses[2].reasons, vec!["no inbound to match tun-in", "no process to match process_name"]);
+        assert_eq!(explanation.misses[3].reasons, vec!["corp is under no domain_suffix and 2001:db8::1 is not private", "port 80 is not 443"]);
+
+        // Without route.final, unmatched traffic goes to the first outbound
+        let explanation = config.explain(&Connection { ip: Some("1.1.1.1".parse()?), ..Connection::default() });
+        assert_eq!(explanation.rule, None);
+        assert_eq!(explanation.outbound.as_deref(), Some("direct-out"));
+
+        Ok(())
+    }
+}
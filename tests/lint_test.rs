@@ -0,0 +1,121 @@
+mod common;
+
+use lessvless::models::{Finding, Severity, SingBoxConfig};
+use serde_json::json;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    fn paths(findings: &[Finding]) -> Vec<(Severity, &str)> {
+        findings.iter().map(|finding| (finding.severity, finding.path.as_str())).collect()
+    }
+
+    #[test]
+    fn test_lint_default() -> Result<(), Box<dyn std::error::Error>> {
+        let config = default_config()?;
+        let findings = config.lint();
+        assert_eq!(paths(&findings), vec![
+            (Severity::Warning, "dns.servers[1]"),
+            (Severity::Error, "outbounds[2].server"),
+        ]);
+        assert_eq!(findings[0].message, "\"cloudflare-doh\" has no detour, so its queries go through the proxy \"wh3tduwc\"");
+        assert_eq!(
+            findings[1].to_string(),
+            "error: outbounds[2].server: default_host is resolved by the DNS server \"cloudflare-doh\", which is itself \
+             reached through \"wh3tduwc\" (fix: set \"detour\": \"direct-out\" on DNS server \"cloudflare-doh\", or resolve \
+             proxy servers with a local DNS server)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_tun() -> Result<(), Box<dyn std::error::Error>> {
+        let config: SingBoxConfig = serde_json::from_value(json!({
+            "dns": {
+                "final": "cf",
+                "servers": [
+                    { "address": "local", "tag": "system" },
+                    { "address": "https://1.1.1.1/dns-query", "tag": "cf", "detour": "direct-out" },
+                ],
+            },
+            "inbounds": [
+                { "tag": "tun-in", "type": "tun", "auto_route": true },
+                { "tag": "tun-strict", "type": "tun", "strict_route": true },
+            ],
+            "log": { "level": "info" },
+            "outbounds": [
+                { "tag": "direct-out", "type": "direct" },
+                { "tag": "dns-out", "type": "dns" },
+                { "tag": "t", "type": "trojan", "server": "t.tld", "server_port": 443, "password": "secret" },
+            ],
+            "route": {
+                "auto_detect_interface": false,
+                "final": "t",
+                "rules": [{ "port": 853, "outbound": "dns-out" }],
+            },
+        }))?;
+        let findings = config.lint();
+        assert_eq!(paths(&findings), vec![
+            (Severity::Error, "route.rules"),
+            (Severity::Warning, "inbounds[1].strict_route"),
+            (Severity::Error, "route.auto_detect_interface"),
+        ]);
+        assert_eq!(findings[0].fix, "add {\"protocol\": \"dns\", \"action\": \"hijack-dns\"} as the first route rule");
+
+        // A template that leaves auto_detect_interface out still loads and gets flagged
+        let mut value = serde_json::to_value(&config)?;
+        value["route"].as_object_mut().unwrap().remove("auto_detect_interface");
+        let missing: SingBoxConfig = serde_json::from_value(value)?;
+        let findings = missing.lint();
+        assert_eq!(findings[2].path, "route.auto_detect_interface");
+        assert!(findings[2].message.starts_with("is missing"), "{}", findings[2].message);
+        assert_eq!(findings[2].fix, "set route.auto_detect_interface to true");
+
+        // Hijacking DNS by action or by rule outbound, and binding the outgoing interface, clear them
+        for (rules, route_extra) in [
+            (json!([{ "protocol": "dns", "action": "hijack-dns" }]), json!({ "auto_detect_interface": true })),
+            (json!([{ "port": 53, "outbound": "dns-out" }]), json!({ "auto_detect_interface": false, "default_interface": "eth0" })),
+        ] {
+            let mut value = serde_json::to_value(&config)?;
+            value["inbounds"][1]["auto_route"] = json!(true);
+            value["route"]["rules"] = rules;
+            for (key, setting) in route_extra.as_object().unwrap() {
+                value["route"][key] = setting.clone();
+            }
+            let config: SingBoxConfig = serde_json::from_value(value)?;
+            assert_eq!(config.lint(), vec![]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_domain_resolver() -> Result<(), Box<dyn std::error::Error>> {
+        let config: SingBoxConfig = serde_json::from_value(json!({
+            "dns": {
+                "final": "system",
+                "servers": [
+                    { "address": "local", "tag": "system" },
+                    { "address": "tls://8.8.8.8", "tag": "google", "detour": "auto" },
+                ],
+            },
+            "inbounds": [],
+            "log": { "level": "info" },
+            "outbounds": [
+                { "tag": "direct-out", "type": "direct" },
+                { "tag": "auto", "type": "urltest", "outbounds": ["a", "b", "c"] },
+                { "tag": "a", "type": "trojan", "server": "a.tld", "server_port": 443, "password": "secret", "domain_resolver": "google" },
+                { "tag": "b", "type": "trojan", "server": "b.tld", "server_port": 443, "password": "secret" },
+                { "tag": "c", "type": "trojan", "server": "10.0.0.1", "server_port": 443, "password": "secret", "domain_resolver": { "server": "google" } },
+            ],
+            "route": { "auto_detect_interface": true, "final": "direct-out", "rules": [] },
+        }))?;
+        let findings = config.lint();
+        assert_eq!(paths(&findings), vec![(Severity::Error, "outbounds[2].server")]);
+        assert_eq!(findings[0].message, "a.tld is resolved by the DNS server \"google\", which is itself reached through \"auto\"");
+
+        Ok(())
+    }
+}
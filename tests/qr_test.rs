@@ -0,0 +1,158 @@
+mod common;
+
+use image::{imageops, GrayImage, Luma};
+use lessvless::qr::{decode_image, decode_luma, render_terminal, write_image};
+use qrcode::{EcLevel, QrCode};
+use lessvless::url_parser::{format_remote_profile_link, parse_url};
+use lessvless::Error;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    const LINK: &str = "trojan://secret@host.tld:443?sni=host.tld#Node";
+
+    #[test]
+    fn test_render_terminal() -> Result<(), Box<dyn std::error::Error>> {
+        let rendered = render_terminal(LINK)?;
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        // 17 + 4 * version modules plus a 4-module quiet zone on each side
+        let width = lines[0].chars().count();
+        assert_eq!((width - 8 - 17) % 4, 0);
+        assert!(lines.iter().all(|line| line.chars().count() == width));
+        // Two rows of modules per line
+        assert_eq!(lines.len(), width.div_ceil(2));
+        assert!(rendered.chars().all(|c| matches!(c, ' ' | '▀' | '▄' | '█' | '\n')));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_image() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("lessvless-qr-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let png = dir.join("link.png");
+        write_image(LINK, &png)?;
+        let image = image::open(&png)?;
+        assert!(image.width() >= 320 && image.width() == image.height());
+
+        let svg = dir.join("link.SVG");
+        write_image(LINK, &svg)?;
+        assert!(std::fs::read_to_string(&svg)?.contains("<svg"));
+
+        let err = write_image(LINK, dir.join("link.gif")).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedImageFormat(_)), "{:?}", err);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_profile_link() -> Result<(), Box<dyn std::error::Error>> {
+        let link = format_remote_profile_link("https://host.tld/profile.json?token=a&b=c", Some("Home VPN"));
+        assert_eq!(link, "sing-box://import-remote-profile?url=https%3A%2F%2Fhost.tld%2Fprofile.json%3Ftoken%3Da%26b%3Dc#Home%20VPN");
+
+        let parsed = parse_url(&link)?;
+        assert_eq!(parsed.param("url"), Some("https://host.tld/profile.json?token=a&b=c"));
+        assert_eq!(parsed.fragment.as_deref(), Some("Home VPN"));
+
+        // Too long for any QR code version
+        let err = render_terminal(&"x".repeat(8000)).unwrap_err();
+        assert!(matches!(err, Error::QrEncode(_)), "{:?}", err);
+
+        Ok(())
+    }
+
+    const VLESS: &str = "vless://0b5f3e2c-5a6e-4c0f-9a3b-7e9d1c2b4a11@example.com:443?encryption=none&security=reality&sni=www.microsoft.com&fp=chrome&pbk=iBXtaHGkwadJMtkWYZxMRfqLLAuDvTHKsHQiLFXXJniM&sid=6ba85179e30d4fc2&type=tcp&flow=xtls-rprx-vision#My%20Server";
+    const SS: &str = "ss://YWVzLTI1Ni1nY206c2VjcmV0@203.0.113.7:8388#SS";
+
+    fn render(data: &str, level: EcLevel, scale: u32) -> Result<GrayImage, Box<dyn std::error::Error>> {
+        let code = QrCode::with_error_correction_level(data.as_bytes(), level)?;
+        Ok(code.render::<Luma<u8>>().module_dimensions(scale, scale).build())
+    }
+
+    #[test]
+    fn test_decode_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+            for data in [LINK, VLESS, SS, "0123456789", "HTTP://HOST.TLD/"] {
+                for scale in [1, 3, 5] {
+                    assert_eq!(decode_luma(&render(data, level, scale)?), vec![data], "{:?} {}", level, scale);
+                }
+            }
+        }
+
+        // Screenshots are rotated, rescaled and colored
+        let image = render(VLESS, EcLevel::M, 3)?;
+        assert_eq!(decode_luma(&imageops::rotate90(&image)), vec![VLESS]);
+        assert_eq!(decode_luma(&imageops::rotate180(&image)), vec![VLESS]);
+        let (width, height) = image.dimensions();
+        let resized = imageops::resize(&image, width * 17 / 10, height * 17 / 10, imageops::FilterType::Triangle);
+        assert_eq!(decode_luma(&resized), vec![VLESS]);
+
+        let mut inverted = GrayImage::from_pixel(width + 40, height + 40, Luma([0]));
+        let mut light = image.clone();
+        imageops::invert(&mut light);
+        imageops::overlay(&mut inverted, &light, 20, 20);
+        assert_eq!(decode_luma(&inverted), vec![VLESS]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_several_codes() -> Result<(), Box<dyn std::error::Error>> {
+        let mut image = GrayImage::from_pixel(800, 700, Luma([230]));
+        imageops::overlay(&mut image, &render(SS, EcLevel::L, 5)?, 100, 380);
+        imageops::overlay(&mut image, &render(LINK, EcLevel::M, 4)?, 20, 30);
+        imageops::overlay(&mut image, &render(VLESS, EcLevel::Q, 3)?, 400, 20);
+        assert_eq!(decode_luma(&image), vec![VLESS, LINK, SS]);
+
+        // Covered modules are restored by error correction
+        let mut damaged = render(VLESS, EcLevel::H, 4)?;
+        let (width, height) = damaged.dimensions();
+        for y in height / 2 - 12..height / 2 + 12 {
+            for x in width / 2 - 12..width / 2 + 12 {
+                damaged.put_pixel(x, y, Luma([0]));
+            }
+        }
+        assert_eq!(decode_luma(&damaged), vec![VLESS]);
+
+        assert!(decode_luma(&GrayImage::from_pixel(200, 200, Luma([255]))).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_image() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("lessvless-qr-decode-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let png = dir.join("link.png");
+        write_image(VLESS, &png)?;
+        assert_eq!(decode_image(&png)?, vec![VLESS]);
+
+        let jpeg = dir.join("link.jpg");
+        render(LINK, EcLevel::M, 4)?.save(&jpeg)?;
+        assert_eq!(decode_image(&jpeg)?, vec![LINK]);
+
+        let blank = dir.join("blank.png");
+        GrayImage::from_pixel(64, 64, Luma([255])).save(&blank)?;
+        let err = decode_image(&blank).unwrap_err();
+        assert!(matches!(err, Error::NoQrCode(ref path) if *path == blank), "{:?}", err);
+
+        let err = decode_image(dir.join("missing.png")).unwrap_err();
+        assert!(matches!(err, Error::Image { .. }), "{:?}", err);
+
+        // Each decoded link becomes its own outbound
+        let mut config = default_config()?;
+        let mut image = GrayImage::from_pixel(600, 300, Luma([255]));
+        imageops::overlay(&mut image, &render(VLESS, EcLevel::M, 2)?, 10, 10);
+        imageops::overlay(&mut image, &render(SS, EcLevel::M, 3)?, 300, 10);
+        let report = config.import_links(&decode_luma(&image));
+        assert_eq!(report.imported, vec!["My Server", "SS"]);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
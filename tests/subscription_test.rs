@@ -0,0 +1,293 @@
+mod common;
+
+use lessvless::models::{GroupOptions, GroupStrategy, SingBoxConfig};
+use lessvless::subscription::{decode_json_subscription, decode_subscription, is_json_subscription};
+use lessvless::Error;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    const SUBSCRIPTION: &str = "dmxlc3M6Ly8wYjVmM2UyYy01YTZlLTRjMGYtOWEzYi03ZTlkMWMyYjRhMTFAYS5ob3N0LnRsZDo0NDM/c2VjdXJpdHk9cmVhbGl0eSZwYms9a2V5JnNpZD1hYiZzbmk9d3d3Lm1pY3Jvc29mdC5jb20mZnA9Y2hyb21lJmZsb3c9eHRscy1ycHJ4LXZpc2lvbiNOb2RlCnRyb2phbjovL3NlY3JldEBiLmhvc3QudGxkOjQ0MyNOb2RlCgpzb2NrczovL3VzZXJAYy5ob3N0LnRsZDoxMDgwI1NvY2tzCnZsZXNzOi8vZC5ob3N0LnRsZDo0NDMjTm8lMjB1dWlkCnNzOi8vWVdWekxUSTFOaTFuWTIwNmMyVmpjbVYwQGUuaG9zdC50bGQ6ODM4OAo=";
+
+    #[test]
+    fn test_decode_subscription() -> Result<(), Box<dyn std::error::Error>> {
+        let links = decode_subscription(SUBSCRIPTION)?;
+        assert_eq!(links.len(), 5);
+        assert!(links[0].starts_with("vless://"));
+        assert_eq!(links[1], "trojan://secret@b.host.tld:443#Node");
+
+        // Plain lists are taken as they are
+        let links = decode_subscription("  trojan://a@b.tld:443\r\n\r\nss://x@y.tld:1\n")?;
+        assert_eq!(links, vec!["trojan://a@b.tld:443", "ss://x@y.tld:1"]);
+
+        let err = decode_subscription("!!! not base64 !!!").unwrap_err();
+        assert!(matches!(err, Error::Parse { .. }), "{:?}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_links() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        let report = config.import_links(&decode_subscription(SUBSCRIPTION)?);
+
+        assert_eq!(report.imported, vec!["Node", "Node-2", "shadowsocks"]);
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(report.skipped[0].line, 3);
+        assert!(matches!(report.skipped[0].reason, Error::UnsupportedProtocol(ref p) if p == "socks"));
+        assert_eq!(report.skipped[1].line, 4);
+        assert!(matches!(report.skipped[1].reason, Error::InvalidLink { ref field, .. } if field == "uuid"));
+        assert!(report.dropped.is_empty());
+
+        let json = serde_json::to_value(&config)?;
+        let outbounds = json["outbounds"].as_array().unwrap();
+        let tags: Vec<&str> = outbounds.iter().map(|out| out["tag"].as_str().unwrap()).collect();
+        // The template's placeholder vless outbound is replaced
+        assert_eq!(tags, vec!["direct-out", "dns-out", "Node", "Node-2", "shadowsocks"]);
+        assert_eq!(outbounds[2]["server"], "a.host.tld");
+        // Defaults the link doesn't set still come from the template's vless outbound
+        assert_eq!(outbounds[2]["packet_encoding"], "xudp");
+        assert_eq!(outbounds[3]["type"], "trojan");
+        assert_eq!(outbounds[4]["method"], "aes-256-gcm");
+        assert_eq!(json["route"]["final"], "Node");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_repoints_detours() -> Result<(), Box<dyn std::error::Error>> {
+        let mut json = serde_json::to_value(default_config()?)?;
+        json["dns"]["servers"][1]["detour"] = serde_json::json!("wh3tduwc");
+        json["outbounds"][0]["detour"] = serde_json::json!("wh3tduwc");
+        let mut config: SingBoxConfig = serde_json::from_value(json)?;
+
+        config.import_links(&decode_subscription(SUBSCRIPTION)?);
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(json["dns"]["servers"][1]["detour"], "Node");
+        assert_eq!(json["outbounds"][0]["detour"], "Node");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_links_nothing_usable() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        let before = serde_json::to_value(&config)?;
+
+        let report = config.import_links(&["socks://a@b.tld:1".to_string(), "garbage".to_string()]);
+        assert!(report.imported.is_empty());
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(serde_json::to_value(&config)?, before);
+
+        Ok(())
+    }
+
+    fn outbound<'a>(json: &'a serde_json::Value, tag: &str) -> &'a serde_json::Value {
+        json["outbounds"].as_array().unwrap().iter().find(|out| out["tag"] == tag).unwrap()
+    }
+
+    #[test]
+    fn test_add_groups_both() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        config.import_links(&decode_subscription(SUBSCRIPTION)?);
+        config.add_groups(&GroupOptions {
+            strategy: GroupStrategy::Both,
+            url: Some("https://cp.cloudflare.com".to_string()),
+            interval: Some("5m".to_string()),
+            tolerance: Some(100),
+        });
+
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(*outbound(&json, "auto"), serde_json::json!({
+            "type": "urltest",
+            "tag": "auto",
+            "outbounds": ["Node", "Node-2", "shadowsocks"],
+            "url": "https://cp.cloudflare.com",
+            "interval": "5m",
+            "tolerance": 100
+        }));
+        assert_eq!(*outbound(&json, "select"), serde_json::json!({
+            "type": "selector",
+            "tag": "select",
+            "outbounds": ["auto", "Node", "Node-2", "shadowsocks"],
+            "default": "auto"
+        }));
+        assert_eq!(json["route"]["final"], "select");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_tags_are_reserved() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        let report = config.import_links(&[
+            "trojan://pass@a.example:443#auto".to_string(),
+            "trojan://pass@b.example:443#b".to_string(),
+            "trojan://pass@c.example:443#select".to_string(),
+        ]);
+        assert_eq!(report.imported, vec!["auto-2", "b", "select-2"]);
+
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::Both, ..Default::default() });
+        let json = serde_json::to_value(&config)?;
+        let tags: Vec<&str> = json["outbounds"].as_array().unwrap().iter().map(|out| out["tag"].as_str().unwrap()).collect();
+        assert_eq!(tags, vec!["direct-out", "dns-out", "auto-2", "b", "select-2", "auto", "select"]);
+        assert_eq!(outbound(&json, "auto")["outbounds"], serde_json::json!(["auto-2", "b", "select-2"]));
+        assert_eq!(outbound(&json, "select")["outbounds"], serde_json::json!(["auto", "auto-2", "b", "select-2"]));
+        assert_eq!(outbound(&json, "auto-2")["server"], "a.example");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_groups_keep_clear_of_other_outbounds() -> Result<(), Box<dyn std::error::Error>> {
+        let mut json = serde_json::to_value(default_config()?)?;
+        json["outbounds"].as_array_mut().unwrap().push(serde_json::json!({ "type": "direct", "tag": "auto" }));
+        let mut config: SingBoxConfig = serde_json::from_value(json)?;
+        config.import_links(&decode_subscription(SUBSCRIPTION)?);
+
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::Both, ..Default::default() });
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(outbound(&json, "auto")["type"], "direct");
+        assert_eq!(outbound(&json, "auto-2")["outbounds"], serde_json::json!(["Node", "Node-2", "shadowsocks"]));
+        assert_eq!(outbound(&json, "select")["outbounds"], serde_json::json!(["auto-2", "Node", "Node-2", "shadowsocks"]));
+        assert_eq!(outbound(&json, "select")["default"], "auto-2");
+        assert_eq!(json["route"]["final"], "select");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_groups_single_strategy() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = default_config()?;
+        config.import_links(&decode_subscription(SUBSCRIPTION)?);
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::UrlTest, ..Default::default() });
+
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(*outbound(&json, "auto"), serde_json::json!({
+            "type": "urltest",
+            "tag": "auto",
+            "outbounds": ["Node", "Node-2", "shadowsocks"]
+        }));
+        assert!(json["outbounds"].as_array().unwrap().iter().all(|out| out["type"] != "selector"));
+        assert_eq!(json["route"]["final"], "auto");
+
+        let mut config = default_config()?;
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::None, ..Default::default() });
+        assert_eq!(serde_json::to_value(&config)?, serde_json::to_value(default_config()?)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_groups_follow_import() -> Result<(), Box<dyn std::error::Error>> {
+        let mut json = serde_json::to_value(default_config()?)?;
+        json["outbounds"].as_array_mut().unwrap().push(serde_json::json!({
+            "type": "selector",
+            "tag": "select",
+            "outbounds": ["wh3tduwc", "direct-out"],
+            "default": "wh3tduwc",
+            "interrupt_exist_connections": true
+        }));
+        let mut config: SingBoxConfig = serde_json::from_value(json)?;
+
+        config.import_links(&decode_subscription(SUBSCRIPTION)?);
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(*outbound(&json, "select"), serde_json::json!({
+            "type": "selector",
+            "tag": "select",
+            "outbounds": ["direct-out", "Node", "Node-2", "shadowsocks"],
+            "interrupt_exist_connections": true
+        }));
+
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::Selector, ..Default::default() });
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(outbound(&json, "select")["outbounds"], serde_json::json!(["Node", "Node-2", "shadowsocks"]));
+        assert_eq!(outbound(&json, "select")["interrupt_exist_connections"], true);
+        assert_eq!(json["outbounds"].as_array().unwrap().len(), 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_sip008() -> Result<(), Box<dyn std::error::Error>> {
+        let body = r#"{
+            "version": 1,
+            "servers": [
+                {"id": "1", "remarks": "wh3tduwc", "server": "a.host.tld", "server_port": 8388, "password": "secret", "method": "aes-256-gcm"},
+                {"id": "2", "remarks": "Obfs", "server": "b.host.tld", "server_port": 8389, "password": "secret", "method": "chacha20-ietf-poly1305", "plugin": "obfs-local", "plugin_opts": "obfs=http;obfs-host=bing.com"},
+                {"id": "3", "remarks": "Again", "server": "a.host.tld", "server_port": 8388, "password": "other", "method": "aes-256-gcm"},
+                {"id": "4", "remarks": "Weak", "server": "c.host.tld", "server_port": 8388, "password": "secret", "method": "table"}
+            ]
+        }"#;
+        assert!(is_json_subscription(body));
+
+        let mut config = default_config()?;
+        let before = serde_json::to_value(&config)?;
+        let report = config.import_outbounds(decode_json_subscription(body)?);
+
+        // The template's placeholder vless outbound is replaced, so its tag is free
+        assert_eq!(report.imported, vec!["wh3tduwc", "Obfs"]);
+        assert_eq!(report.skipped.len(), 2);
+        assert_eq!(report.skipped[0].line, 3);
+        assert_eq!(report.skipped[0].link, "Again");
+        assert!(matches!(report.skipped[0].reason, Error::DuplicateServer { ref tag, .. } if tag == "wh3tduwc"));
+        assert!(matches!(report.skipped[1].reason, Error::InvalidLink { ref field, .. } if field == "method"));
+
+        let json = serde_json::to_value(&config)?;
+        let outbounds = json["outbounds"].as_array().unwrap();
+        // The non-proxy outbounds are kept as they were
+        assert_eq!(outbounds[..2], before["outbounds"].as_array().unwrap()[..2]);
+        assert_eq!(outbounds.len(), 4);
+        assert_eq!(outbound(&json, "wh3tduwc")["type"], "shadowsocks");
+        assert_eq!(outbound(&json, "wh3tduwc")["server"], "a.host.tld");
+        assert_eq!(outbound(&json, "Obfs")["plugin"], "obfs-local");
+        assert_eq!(outbound(&json, "Obfs")["plugin_opts"], "obfs=http;obfs-host=bing.com");
+        assert_eq!(json["route"], before["route"]);
+
+        // Only the imported servers are grouped
+        config.add_groups(&GroupOptions { strategy: GroupStrategy::Both, ..Default::default() });
+        let json = serde_json::to_value(&config)?;
+        assert_eq!(outbound(&json, "select")["outbounds"], serde_json::json!(["auto", "wh3tduwc", "Obfs"]));
+        assert!(!json.to_string().contains("default_host"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_sing_box_outbounds() -> Result<(), Box<dyn std::error::Error>> {
+        let body = r#"{
+            "outbounds": [
+                {"type": "selector", "tag": "proxy", "outbounds": ["hy2", "same-server"]},
+                {"type": "hysteria2", "tag": "hy2", "server": "h.host.tld", "server_port": 443, "password": "p", "tls": {"enabled": true}},
+                {"type": "trojan", "tag": "same-server", "server": "default_host", "server_port": 8081, "password": "p"},
+                {"type": "wireguard", "tag": "wg", "server": "w.host.tld", "server_port": 51820},
+                {"type": "direct", "tag": "direct-out"}
+            ]
+        }"#;
+        assert!(!is_json_subscription("dHJvamFuOi8v"));
+
+        let mut config = default_config()?;
+        let report = config.merge_outbounds(decode_json_subscription(body)?);
+
+        assert_eq!(report.imported, vec!["hy2"]);
+        let reasons: Vec<String> = report.skipped.iter().map(|skipped| skipped.reason.to_string()).collect();
+        assert_eq!(reasons, vec![
+            "default_host:8081 is already used by outbound \"wh3tduwc\"".to_string(),
+            "unsupported protocol: \"wireguard\"".to_string(),
+        ]);
+
+        let tags: Vec<&str> = config.outbounds().iter().map(|out| out.tag()).collect();
+        assert_eq!(tags, vec!["direct-out", "dns-out", "wh3tduwc", "hy2"]);
+
+        // A bare array is accepted too
+        let entries = decode_json_subscription(r#"[{"type": "tuic", "tag": "t", "server": "t.tld", "server_port": 1, "uuid": "u", "password": "p"}]"#)?;
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].1.is_ok());
+
+        let err = decode_json_subscription(r#"{"version": 1}"#).unwrap_err();
+        assert!(matches!(err, Error::InvalidSubscription(_)), "{:?}", err);
+
+        Ok(())
+    }
+}
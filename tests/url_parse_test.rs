@@ -1,28 +1,237 @@
-use lessvless::url_parser::parse_url;
-use serde_json::Map;
+use lessvless::url_parser::{parse_share_link, parse_url, parse_url_params, percent_decode, redact_link, Host, ParseError};
+use lessvless::Error;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 mod tests {
-    use super::*;  
+    use super::*;
+
+    fn parse_error<T: std::fmt::Debug>(result: Result<T, Error>) -> ParseError {
+        match result {
+            Err(Error::Parse { kind, .. }) => kind,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
 
     #[test]
     fn test_parse_url() -> Result<(), Box<dyn std::error::Error>> {
         let url = "vless://host.tld:443?param1=value1&param2=value2".to_string();
-        let json: Map<String, serde_json::Value> = parse_url(&url)?; 
+        let link = parse_url(&url)?;
         // Assert protocol
-        assert_eq!(json.get("protocol").unwrap(), "vless");
+        assert_eq!(link.scheme, "vless");
 
         // Assert hostname
-        assert_eq!(json.get("host").unwrap(), "host.tld");
+        assert_eq!(link.host, Host::Domain("host.tld".to_string()));
 
         // Assert port
-        assert_eq!(json.get("port").unwrap(), 443);
+        assert_eq!(link.port, Some(443));
 
         // Assert parameters
-        let params = json.get("params").unwrap().as_object().unwrap();
-        assert_eq!(params.get("param1").unwrap(), "value1");
-        assert_eq!(params.get("param2").unwrap(), "value2");
-    
-        Ok(())        
+        assert_eq!(link.param("param1"), Some("value1"));
+        assert_eq!(link.param("param2"), Some("value2"));
+
+        Ok(())
+
+    }
+
+    #[test]
+    fn test_parse_reality_link() -> Result<(), Box<dyn std::error::Error>> {
+        let url = "vless://0b5f3e2c-5a6e-4c0f-9a3b-7e9d1c2b4a11@example.com:443?security=reality&encryption=none&pbk=iBXtaHGkwadJMtkWYZxMRfqLLAuDvTHKsHQiLFXXJniM&fp=chrome&sni=www.microsoft.com&sid=6ba85179e30d4fc2&type=tcp&flow=xtls-rprx-vision#My%20Server";
+        let link = parse_url(url)?;
+
+        assert_eq!(link.scheme, "vless");
+        assert_eq!(link.user_info.as_deref(), Some("0b5f3e2c-5a6e-4c0f-9a3b-7e9d1c2b4a11"));
+        assert_eq!(link.host, Host::Domain("example.com".to_string()));
+        assert_eq!(link.port, Some(443));
+        assert_eq!(link.param("security"), Some("reality"));
+        assert_eq!(link.param("sid"), Some("6ba85179e30d4fc2"));
+        assert_eq!(link.param("flow"), Some("xtls-rprx-vision"));
+        assert_eq!(link.fragment.as_deref(), Some("My Server"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_uppercase() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("VLESS://0B5F3E2C-5A6E-4C0F-9A3B-7E9D1C2B4A11@Example.COM:8443")?;
+
+        assert_eq!(link.scheme, "vless");
+        // User-info is case sensitive and must be kept verbatim
+        assert_eq!(link.user_info.as_deref(), Some("0B5F3E2C-5A6E-4C0F-9A3B-7E9D1C2B4A11"));
+        assert_eq!(link.host, Host::Domain("example.com".to_string()));
+        assert_eq!(link.port, Some(8443));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ipv4() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("trojan://secret@203.0.113.7:443")?;
+
+        assert_eq!(link.host, Host::Ipv4(Ipv4Addr::new(203, 0, 113, 7)));
+        assert_eq!(link.host.to_string(), "203.0.113.7");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ipv6() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("vless://uuid@[2001:db8::1]:443?security=tls")?;
+
+        assert_eq!(link.host, Host::Ipv6("2001:db8::1".parse::<Ipv6Addr>()?));
+        assert_eq!(link.host.to_string(), "2001:db8::1");
+        assert_eq!(link.port, Some(443));
+        assert_eq!(link.param("security"), Some("tls"));
+
+        let link = parse_url("vless://uuid@[::1]")?;
+        assert_eq!(link.host, Host::Ipv6(Ipv6Addr::LOCALHOST));
+        assert_eq!(link.port, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_percent_encoded() -> Result<(), Box<dyn std::error::Error>> {
+        let url = "trojan://p%40ss%3Aword@host.tld:443?path=%2Fws%3Fed%3D2048&host=cdn.host.tld&alpn=h2%2Chttp%2F1.1#%D0%9C%D0%BE%D1%81%D0%BA%D0%B2%D0%B0";
+        let link = parse_url(url)?;
+
+        assert_eq!(link.user_info.as_deref(), Some("p@ss:word"));
+        assert_eq!(link.param("path"), Some("/ws?ed=2048"));
+        assert_eq!(link.param("alpn"), Some("h2,http/1.1"));
+        assert_eq!(link.fragment.as_deref(), Some("Москва"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_plus_is_not_space() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("ss://host.tld:8388?key=ab+cd/ef==")?;
+
+        assert_eq!(link.param("key"), Some("ab+cd/ef=="));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_user_info_with_at_sign() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("trojan://user@mail@host.tld:443")?;
+
+        assert_eq!(link.user_info.as_deref(), Some("user@mail"));
+        assert_eq!(link.host, Host::Domain("host.tld".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_multi_valued_query() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("vless://uuid@host.tld:443/?alpn=h2&alpn=http/1.1&flag&empty=")?;
+
+        assert_eq!(link.path, "/");
+        assert_eq!(link.param("alpn"), Some("h2"));
+        assert_eq!(link.params("alpn"), vec!["h2", "http/1.1"]);
+        assert_eq!(link.param("flag"), Some(""));
+        assert_eq!(link.param("empty"), Some(""));
+        assert_eq!(link.param("missing"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fragment_only() -> Result<(), Box<dyn std::error::Error>> {
+        let link = parse_url("vless://uuid@host.tld:443#node-1")?;
+
+        assert!(link.query.is_empty());
+        assert_eq!(link.fragment.as_deref(), Some("node-1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_url_params() -> Result<(), Box<dyn std::error::Error>> {
+        let params = parse_url_params("a=1&b=%20two&&c")?;
+
+        assert_eq!(params, vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), " two".to_string()),
+            ("c".to_string(), String::new()),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%2Fb%2fc").unwrap(), "a/b/c");
+        assert_eq!(parse_error(percent_decode("100%")), ParseError::InvalidPercentEncoding("100%".to_string()));
+        assert_eq!(parse_error(percent_decode("%zz")), ParseError::InvalidPercentEncoding("%zz".to_string()));
+        assert_eq!(parse_error(percent_decode("%ff")), ParseError::InvalidUtf8("%ff".to_string()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_error(parse_url("host.tld")), ParseError::MissingScheme);
+        assert_eq!(parse_error(parse_url("://host.tld")), ParseError::MissingScheme);
+        assert_eq!(parse_error(parse_url("1vless://host.tld")), ParseError::InvalidScheme("1vless".to_string()));
+        assert_eq!(parse_error(parse_url("vless:host.tld")), ParseError::MissingAuthority);
+        assert_eq!(parse_error(parse_url("vless://uuid@:443")), ParseError::MissingHost);
+        assert_eq!(parse_error(parse_url("vless://uuid@host.tld:99999")), ParseError::InvalidPort("99999".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@host.tld:44a")), ParseError::InvalidPort("44a".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@[2001:db8::1:443")), ParseError::InvalidHost("[2001:db8::1:443".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@[not-an-ip]:443")), ParseError::InvalidHost("[not-an-ip]".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@[::1]443")), ParseError::InvalidHost("[::1]443".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@2001:db8::1:443")), ParseError::InvalidHost("2001:db8::1".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@bad host:443")), ParseError::InvalidHost("bad host".to_string()));
+        assert_eq!(parse_error(parse_url("vless://uuid@host.tld:443?x=%G0")), ParseError::InvalidPercentEncoding("%G0".to_string()));
+    }
+
+    #[test]
+    fn test_parse_errors_redact_credentials() {
+        let err = parse_url("vless://0b5f3e2c-5a6e@host.tld:99999?sni=a.tld#Node").unwrap_err();
+        assert_eq!(err.to_string(), "failed to parse \"vless://***@host.tld:99999?sni=a.tld#Node\": invalid port: \"99999\"");
+        let err = parse_url("trojan://pa%zzss@host.tld:443").unwrap_err();
+        assert_eq!(err.to_string(), "failed to parse \"trojan://***@host.tld:443\": invalid percent-encoding in \"***\"");
+
+        let err = parse_url("hysteria2://host.tld:443?obfs=salamander&obfs-password=SECRETPW&sni=%zz").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to parse \"hysteria2://host.tld:443?obfs=salamander&obfs-password=***&sni=%zz\": invalid percent-encoding in \"%zz\""
+        );
+        assert!(!format!("{:?}", err).contains("SECRETPW"), "{:?}", err);
+        let err = parse_url("hysteria2://host.tld:443?auth=pa%zz").unwrap_err();
+        assert_eq!(err.to_string(), "failed to parse \"hysteria2://host.tld:443?auth=***\": invalid percent-encoding in \"***\"");
+        let err = parse_url_params("password=%zz&sni=a.tld").unwrap_err();
+        assert!(!format!("{} {:?}", err, err).contains("%zz"), "{:?}", err);
+
+        let err = parse_share_link("ss://c2VjcmV0/w#Old").unwrap_err();
+        assert!(!err.to_string().contains("c2VjcmV0"), "{}", err);
+
+        assert_eq!(redact_link("vmess://eyJpZCI6ICJ1dWlkIn0=#n"), "vmess://***#n");
+        assert_eq!(redact_link("host.tld:443"), "host.tld:443");
+        assert_eq!(redact_link("hysteria2://h.tld:443/?Auth=x#n"), "hysteria2://h.tld:443/?Auth=***#n");
+    }
+
+    #[test]
+    fn test_parse_share_link_vmess() -> Result<(), Box<dyn std::error::Error>> {
+        // Base64 of {"ps": "n", "add": "Vm.Host.tld", "port": "443", "id": "uuid", "net": "grpc", "path": "svc", "type": "multi", "tls": "tls"}
+        let link = parse_share_link("vmess://eyJwcyI6ICJuIiwgImFkZCI6ICJWbS5Ib3N0LnRsZCIsICJwb3J0IjogIjQ0MyIsICJpZCI6ICJ1dWlkIiwgIm5ldCI6ICJncnBjIiwgInBhdGgiOiAic3ZjIiwgInR5cGUiOiAibXVsdGkiLCAidGxzIjogInRscyJ9")?;
+
+        assert_eq!(link.scheme, "vmess");
+        assert_eq!(link.user_info.as_deref(), Some("uuid"));
+        assert_eq!(link.host, Host::Domain("vm.host.tld".to_string()));
+        assert_eq!(link.port, Some(443));
+        assert_eq!(link.param("type"), Some("grpc"));
+        assert_eq!(link.param("serviceName"), Some("svc"));
+        assert_eq!(link.param("mode"), Some("multi"));
+        assert_eq!(link.param("security"), Some("tls"));
+        assert_eq!(link.fragment.as_deref(), Some("n"));
+
+        // Links in the RFC 3986 shape are not base64 decoded
+        let link = parse_share_link("vmess://uuid@host.tld:443?type=ws")?;
+        assert_eq!(link.user_info.as_deref(), Some("uuid"));
+        assert_eq!(link.param("type"), Some("ws"));
+
+        assert_eq!(parse_error(parse_share_link("vmess://%%%")), ParseError::InvalidBase64);
+        assert!(matches!(parse_error(parse_share_link("vmess://bm90IGpzb24")), ParseError::InvalidVmessJson(_)));
 
+        Ok(())
     }
 }
@@ -0,0 +1,71 @@
+mod common;
+
+use lessvless::models::{Diagnostic, SingBoxConfig};
+use serde_json::json;
+use common::default_config;
+
+mod tests {
+    use super::*;
+
+    fn paths(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|diagnostic| diagnostic.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_validate_default() -> Result<(), Box<dyn std::error::Error>> {
+        let config = default_config()?;
+        assert_eq!(config.validate(), vec![]);
+
+        // Generated configs stay consistent
+        let mut config = default_config()?;
+        config.import_links(&["trojan://pass@a.tld:443#A".to_string(), "trojan://pass@b.tld:443#A".to_string()]);
+        assert_eq!(config.validate(), vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_cross_references() -> Result<(), Box<dyn std::error::Error>> {
+        let config: SingBoxConfig = serde_json::from_value(json!({
+            "dns": { "final": "cf", "servers": [{ "address": "local", "tag": "system" }] },
+            "inbounds": [{ "tag": "direct-out", "type": "mixed", "listen": "127.0.0.1", "listen_port": 70000 }],
+            "log": { "level": "info" },
+            "outbounds": [
+                { "tag": "direct-out", "type": "direct" },
+                { "tag": "select", "type": "selector", "outbounds": ["direct-out", "gone"], "default": "nope" },
+                { "tag": "t", "type": "trojan", "server": "t.tld", "server_port": 0, "password": "secret" },
+            ],
+            "route": {
+                "auto_detect_interface": true,
+                "default_domain_resolver": "cloudflare-doh",
+                "final": "typo",
+                "rules": [
+                    { "ip_cidr": ["10.0.0.0/33", "1.1.1.1", "::/0", "10.0.0.0/8", "bad", "10.0.0.0/+8"], "outbound": "t2" },
+                    { "port": 0, "outbound": "t" },
+                ],
+            },
+        }))?;
+        let diagnostics = config.validate();
+        assert_eq!(paths(&diagnostics), vec![
+            "outbounds[0].tag",
+            "outbounds[1].outbounds[1]",
+            "outbounds[1].default",
+            "route.final",
+            "route.rules[0].outbound",
+            "dns.final",
+            "route.default_domain_resolver",
+            "route.rules[0].ip_cidr[0]",
+            "route.rules[0].ip_cidr[4]",
+            "route.rules[0].ip_cidr[5]",
+            "route.rules[1].port",
+            "inbounds[0].listen_port",
+            "outbounds[2].server_port",
+        ]);
+        assert_eq!(diagnostics[0].to_string(), "outbounds[0].tag: tag \"direct-out\" is already used by inbounds[0]");
+        assert_eq!(diagnostics[3].message, "no outbound is tagged \"typo\"");
+        assert_eq!(diagnostics[5].message, "no DNS server is tagged \"cf\"");
+        assert_eq!(diagnostics[10].message, "port 0 is outside 1-65535");
+
+        Ok(())
+    }
+}